@@ -1,4 +1,6 @@
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use mind_tree::node::{query::Query, SortKey, TaskStatus};
+use serde::Deserialize;
 use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
@@ -6,7 +8,9 @@ use std::path::PathBuf;
   author = "Dimitri Sabadie <dimitri.sabadie@gmail.com>",
   name = "mind",
   version,
-  about = "Organize your thoughts in a tree-like structure"
+  about = "Organize your thoughts in a tree-like structure",
+  after_help = "Nodes are selected either by path, e.g. /a/b/c, or by ID, e.g. id:<ID>; cloned nodes share their ID, so \
+                their occurrences are selected with id:<ID>[N]."
 )]
 pub struct Cli {
   #[command(subcommand)]
@@ -47,15 +51,96 @@ pub struct DataArgs {
   #[arg(short, long)]
   pub uri: Option<Option<String>>,
 
+  /// Reference another node of the same tree, selected by path or ID.
+  #[arg(short, long = "ref", conflicts_with_all = ["file", "uri"])]
+  pub reference: Option<String>,
+
+  /// Detach the attachment at the given position (starting at 1).
+  #[arg(long, conflicts_with_all = ["file", "uri", "open"])]
+  pub detach: Option<usize>,
+
   /// Open a node if it contains data.
   ///
   /// “Opening” is contextual: if the node is a file node, the file will be edited with your editor (either via the
   /// $EDITOR environment variable, or via the edit.editor configuration path). If it’s a link node, a command used
-  /// to open URI will be used, depending on your operating system.
+  /// to open URI will be used, depending on your operating system. If it’s a reference to another node, the data of
+  /// that node is opened instead.
   #[arg(short, long)]
   pub open: bool,
 }
 
+impl DataArgs {
+  /// Whether any data is to be attached.
+  pub fn any(&self) -> bool {
+    self.file || self.uri.is_some() || self.reference.is_some()
+  }
+}
+
+/// Node metadata arguments.
+#[derive(Args, Debug)]
+pub struct MetaArgs {
+  /// Set the due date of the node.
+  #[arg(long)]
+  pub due: Option<String>,
+
+  /// Set the scheduled date of the node.
+  #[arg(long)]
+  pub scheduled: Option<String>,
+
+  /// Set the body text of the node.
+  ///
+  /// The body is read from the standard input if it’s not a terminal; otherwise, your editor is opened to edit the
+  /// current body. An empty body clears it.
+  #[arg(long)]
+  pub body: bool,
+}
+
+impl MetaArgs {
+  /// Whether any metadata is to be set.
+  pub fn any(&self) -> bool {
+    self.due.is_some() || self.scheduled.is_some() || self.body
+  }
+}
+
+/// Node information arguments.
+///
+/// When any of those is set, the requested information is printed, one per line, instead of the data of the node.
+#[derive(Args, Debug)]
+pub struct InfoArgs {
+  /// Print the ID of the node.
+  ///
+  /// The ID never changes, even if the node is renamed or moved, so it can be used to select the node later.
+  #[arg(long)]
+  pub id: bool,
+
+  /// Print the date and time at which the node was created.
+  #[arg(long)]
+  pub created: bool,
+
+  /// Print the date and time at which the node was last modified.
+  #[arg(long)]
+  pub modified: bool,
+
+  /// Print the due date of the node.
+  #[arg(long)]
+  pub due: bool,
+
+  /// Print the scheduled date of the node.
+  #[arg(long)]
+  pub scheduled: bool,
+
+  /// Print the body text of the node.
+  #[arg(long)]
+  pub body: bool,
+}
+
+impl InfoArgs {
+  /// Whether any information was requested.
+  pub fn any(&self) -> bool {
+    self.id || self.created || self.modified || self.due || self.scheduled || self.body
+  }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
   /// Initialize a new Mind tree.
@@ -156,6 +241,52 @@ pub enum Command {
     dest: Option<String>,
   },
 
+  /// Clone a node into another one.
+  ///
+  /// The clone shares everything with the source node (name, icon, data, etc.), so that modifying one modifies the
+  /// other; only their positions and children differ: the clone starts without children, and children added to one of
+  /// them don’t appear in the other one. They also share their ID; use id:<ID>[N] to select one of them by ID.
+  Clone {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    #[arg(default_value_t, short, value_enum)]
+    mode: InsertMode,
+
+    /// Source path.
+    #[arg(short, long)]
+    source: Option<String>,
+
+    /// Destination path.
+    #[arg(short, long)]
+    dest: Option<String>,
+  },
+
+  /// Copy a node and all its children into another node.
+  ///
+  /// Unlike clones, the copies are new nodes, independent from the source ones. Attached data files are shared with the
+  /// source nodes, unless --data-files is passed.
+  #[command(alias = "cp")]
+  Copy {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    #[arg(default_value_t, short, value_enum)]
+    mode: InsertMode,
+
+    /// Source path.
+    #[arg(short, long)]
+    source: Option<String>,
+
+    /// Destination path.
+    #[arg(short, long)]
+    dest: Option<String>,
+
+    /// Duplicate the attached data files as well.
+    #[arg(long)]
+    data_files: bool,
+  },
+
   /// Get all paths in a given node.
   Paths {
     #[command(flatten)]
@@ -169,12 +300,52 @@ pub enum Command {
     #[arg(short, long)]
     uri: bool,
 
+    /// Filter by nodes having the given tag.
+    #[arg(short, long, conflicts_with_all = ["file", "uri", "prop"])]
+    tag: Option<String>,
+
+    /// Filter by nodes having the given property, either as key or key=value.
+    #[arg(long, conflicts_with_all = ["file", "uri", "tag"])]
+    prop: Option<String>,
+
+    /// Filter by nodes matching the given query; see the query command.
+    #[arg(short, long, conflicts_with_all = ["file", "uri", "tag", "prop"])]
+    query: Option<Query>,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+  },
+
+  /// Get the paths of the nodes matching a query.
+  ///
+  /// Queries are made of predicates, combined with and, or and not (or &&, || and !) and grouped with parentheses;
+  /// predicates next to each other are combined with and. The predicates are:
+  ///
+  ///   name:<glob>, name:~<regex>      name of the node
+  ///   depth:<n>, depth:<=<n>, …       depth of the node, the root being at depth 0
+  ///   has-children, leaf              whether the node has children
+  ///   data:<file|link|node|any|none>  kind of data attached to the node
+  ///   under:<path>                    the node is below the given path
+  ///   tag:<tag>, prop:<key[=value]>   tags and properties of the node
+  ///
+  /// Values with spaces or parentheses must be quoted, e.g. name:"CI (old)".
+  #[command(verbatim_doc_comment)]
+  Query {
+    #[command(flatten)]
+    common_args: CommonArgs,
+
+    /// Query to match nodes with, e.g. 'under:/Projects and leaf and not data:any'.
+    query: Query,
+
     /// Select a base node to operate on.
     #[arg(short, long)]
     source: Option<String>,
   },
 
   /// Get associated data with a node.
+  ///
+  /// All the attachments of the node are printed, one per line, in the order they were attached.
   Get {
     #[clap(flatten)]
     common_args: CommonArgs,
@@ -191,16 +362,32 @@ pub enum Command {
     ///
     /// “Opening” is contextual: if the node is a file node, the file will be edited with your editor (either via the
     /// $EDITOR environment variable, or via the edit.editor configuration path). If it’s a link node, a command used
-    /// to open URI will be used, depending on your operating system.
+    /// to open URI will be used, depending on your operating system. If it’s a reference to another node, the data of
+    /// that node is opened instead, or its path is printed if it has no file nor link.
+    ///
+    /// If the node has several attachments, the one to open must be selected, either with --attachment or
+    /// interactively.
     #[arg(short, long)]
     open: bool,
 
+    /// Select the attachment at the given position (starting at 1).
+    #[arg(short, long)]
+    attachment: Option<usize>,
+
+    #[clap(flatten)]
+    info_args: InfoArgs,
+
     /// Select a base node to operate on.
     #[arg(short, long)]
     source: Option<String>,
   },
 
   /// Associate data to a node.
+  ///
+  /// Nodes can have several attachments; each call with --file or --uri adds a new one.
+  ///
+  /// Dates are either absolute (YYYY-MM-DD) or relative: today, tomorrow, yesterday, a weekday (e.g. fri) or an
+  /// offset (e.g. +3d, +2w, +1m). Use none to clear a date.
   Set {
     #[clap(flatten)]
     common_args: CommonArgs,
@@ -208,9 +395,249 @@ pub enum Command {
     #[clap(flatten)]
     data_args: DataArgs,
 
+    #[clap(flatten)]
+    meta_args: MetaArgs,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+  },
+
+  /// Manage the tags of a node.
+  Tag {
+    #[command(subcommand)]
+    cmd: TagCommand,
+  },
+
+  /// Manage the properties of a node.
+  Prop {
+    #[command(subcommand)]
+    cmd: PropCommand,
+  },
+
+  /// List the nodes referencing a node.
+  ///
+  /// This command requires a base selection.
+  Backlinks {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+  },
+
+  /// Get or change the task status of a node.
+  ///
+  /// Without --set or --cycle, print the status of the node along with the progress (done/total) of the tasks below
+  /// it.
+  ///
+  /// This command requires a base selection.
+  Status {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
     /// Select a base node to operate on.
     #[arg(short, long)]
     source: Option<String>,
+
+    /// Set the status of the node.
+    #[arg(long, value_enum, conflicts_with = "cycle")]
+    set: Option<Status>,
+
+    /// Cycle the status of the node (none → todo → doing → done → cancelled → none).
+    #[arg(long)]
+    cycle: bool,
+  },
+
+  /// Sort the children of a node.
+  ///
+  /// The sort is stable: children comparing equal keep their relative order.
+  ///
+  /// This command requires a base selection.
+  Sort {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+
+    /// Key to sort the children by.
+    #[arg(default_value_t, long, value_enum)]
+    by: SortBy,
+
+    /// Also sort the children of the children, recursively.
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Sort in reverse order.
+    #[arg(long)]
+    reverse: bool,
+  },
+
+  /// List the most recently modified nodes, most recent first.
+  Recent {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Maximum number of nodes to list.
+    #[arg(default_value_t = 10, short = 'n', long)]
+    count: usize,
+
+    /// Print the modification date and time before each path.
+    #[arg(short, long)]
+    time: bool,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+  },
+
+  /// Print the agenda: overdue, today’s and upcoming items.
+  ///
+  /// The main tree and all the CWD trees are looked up for nodes with a due or scheduled date. Done and cancelled
+  /// tasks are ignored.
+  Agenda {
+    /// Number of days to look ahead for upcoming items.
+    #[arg(default_value_t = 7, short, long)]
+    days: u32,
+  },
+
+  /// Apply a batch of operations to a tree, as a whole.
+  ///
+  /// Operations are read as a JSON list from a file, or from the standard input with -. They are applied in order, so
+  /// that an operation sees the effects of the previous ones; if one of them fails, none is applied. Nodes are
+  /// selected by path or ID, and are never created automatically. Supported operations:
+  ///
+  ///   {"op": "insert", "source": "/a", "name": "b", "mode": "bottom", "icon": "x"}
+  ///   {"op": "move", "source": "/a/b", "dest": "/c", "mode": "top"}
+  ///   {"op": "rename", "source": "/a", "name": "c"}
+  ///   {"op": "remove", "source": "/a"}
+  ///   {"op": "set", "source": "/a", "icon": "x", "status": "todo"}
+  ///
+  /// "mode" is one of top, bottom (default), before and after; "icon" is optional; "set" changes the icon and / or
+  /// the status (none, todo, doing, done or cancelled) of a node.
+  #[command(verbatim_doc_comment)]
+  Apply {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// JSON file containing the operations, or - to read them from the standard input.
+    file: PathBuf,
+  },
+
+  /// Print the structural differences between two trees.
+  ///
+  /// Nodes are matched by ID, so that renamed and moved nodes are reported as such. Added and removed nodes are
+  /// prefixed with + and -, and other changes with ~. Trees saved before nodes had IDs cannot be diffed.
+  Diff {
+    /// Path of the old tree.
+    old: PathBuf,
+
+    /// Path of the new tree.
+    new: PathBuf,
+  },
+
+  /// Merge the changes made to a tree on two sides, from a common ancestor.
+  ///
+  /// Changes made on one side only are merged automatically. Conflicting changes keep the version of ours and are
+  /// marked with nodes named "<<<<<<< conflict: …", which have the version of each side as children; the command then
+  /// fails, so that the conflicts are resolved by hand. The result is written to ours, unless --output is set. Nodes are
+  /// matched by ID, so trees saved before nodes had IDs cannot be merged: the command fails without writing anything.
+  ///
+  /// This command can be used as a Git merge driver for local trees:
+  ///
+  ///   # .git/config
+  ///   [merge "mind"]
+  ///     name = mind tree merge
+  ///     driver = mind merge-file %O %A %B
+  ///
+  ///   # .gitattributes
+  ///   .mind/state.json merge=mind
+  #[command(verbatim_doc_comment)]
+  MergeFile {
+    /// Path of the common ancestor tree.
+    base: PathBuf,
+
+    /// Path of our tree.
+    ours: PathBuf,
+
+    /// Path of their tree.
+    theirs: PathBuf,
+
+    /// Write the merged tree to this path instead of ours.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+  },
+
+  /// Search node names and the contents of their data files.
+  ///
+  /// The main tree, all the CWD trees and the local tree of the current directory, if any, are searched. Nodes must
+  /// match all the terms, case-insensitively; they are printed by decreasing relevance, with a matching line of their
+  /// data files, if any.
+  Search {
+    /// Terms to search for.
+    #[arg(required = true)]
+    terms: Vec<String>,
+
+    /// Maximum number of results to print.
+    #[arg(default_value_t = 20, short = 'n', long)]
+    limit: usize,
+  },
+
+  /// Clean up the data files that no node is attached to anymore.
+  ///
+  /// The main tree, all the CWD trees, and the local trees of their directories and of the current directory, if any,
+  /// are scanned. Data files of the data directory attached to none of their nodes are moved to the trash directory of
+  /// the data directory, or deleted with --delete. Data files used only by other trees must be protected by passing
+  /// those trees with --tree.
+  Gc {
+    /// Only list the orphaned data files, without touching them.
+    #[arg(long, conflicts_with = "delete")]
+    dry_run: bool,
+
+    /// Delete the orphaned data files instead of moving them to the trash directory.
+    #[arg(long)]
+    delete: bool,
+
+    /// Path of another tree to scan; can be passed several times.
+    #[arg(short, long = "tree")]
+    trees: Vec<PathBuf>,
+  },
+
+  /// Check the integrity of the forest, or of a single tree, and optionally repair it.
+  ///
+  /// Broken parent and sibling links, empty names, missing data files, CWD trees whose directory doesn’t exist anymore
+  /// and unknown tree versions are reported. With --fix, links are repaired, empty names are replaced, and missing data
+  /// files are detached; the other issues must be handled by hand. Siblings sharing the same name are legitimate, so
+  /// they are only reported as notes and never renamed.
+  Doctor {
+    /// Check the tree at this path instead of the forest.
+    #[arg(short, long)]
+    path: Option<PathBuf>,
+
+    /// Repair the issues that can be repaired.
+    #[arg(long)]
+    fix: bool,
+  },
+
+  /// Print statistics about a tree, or about all the trees of the forest.
+  Stats {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Compute the statistics of the main tree and all the CWD trees together.
+    #[arg(short, long, conflicts_with_all = ["path", "cwd", "local"])]
+    forest: bool,
+
+    /// Number of largest subtrees to print.
+    #[arg(default_value_t = 5, short = 'n', long)]
+    largest: usize,
+
+    /// Print the statistics as JSON.
+    #[arg(long)]
+    json: bool,
   },
 
   /// List all the currently known trees.
@@ -224,15 +651,76 @@ pub enum Command {
   },
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+  /// Not a task.
+  None,
+  Todo,
+  Doing,
+  Done,
+  Cancelled,
+}
+
+impl Status {
+  pub fn task_status(self) -> Option<TaskStatus> {
+    match self {
+      Status::None => None,
+      Status::Todo => Some(TaskStatus::Todo),
+      Status::Doing => Some(TaskStatus::Doing),
+      Status::Done => Some(TaskStatus::Done),
+      Status::Cancelled => Some(TaskStatus::Cancelled),
+    }
+  }
+}
+
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum SortBy {
+  /// Name, case-insensitively, with numbers compared by value (`day 2` before `day 10`).
+  #[default]
+  Name,
+
+  /// Icon.
+  Icon,
+
+  /// Kind of data: nodes without data first, then files, links and references.
+  Data,
+
+  /// Nodes with children first.
+  Children,
+
+  /// Date of last modification, oldest first.
+  Date,
+
+  /// Task status (todo, doing, done, cancelled); regular nodes come last.
+  Status,
+}
+
+impl SortBy {
+  pub fn sort_key(self) -> SortKey {
+    match self {
+      SortBy::Name => SortKey::Name,
+      SortBy::Icon => SortKey::Icon,
+      SortBy::Data => SortKey::Data,
+      SortBy::Children => SortKey::Children,
+      SortBy::Date => SortKey::Date,
+      SortBy::Status => SortKey::Status,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum InsertMode {
   /// Insert the node inside the selected node, at the top.
   #[value(name = "top")]
+  #[serde(rename = "top")]
   InsideTop,
 
   /// Insert the node inside the selected node, at the bottom.
   #[default]
   #[value(name = "bottom")]
+  #[serde(rename = "bottom")]
   InsideBottom,
 
   /// Insert the node as a sibling, just before the selected node (if the selected has a parent).
@@ -241,3 +729,141 @@ pub enum InsertMode {
   /// Insert the node as a sibling, just after the selected node (if the selected has a parent)
   After,
 }
+
+/// An operation of a batch applied with `mind apply`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase", deny_unknown_fields)]
+pub enum BatchOperation {
+  Insert {
+    source: String,
+    name: String,
+    #[serde(default)]
+    mode: InsertMode,
+    #[serde(default)]
+    icon: String,
+  },
+
+  Move {
+    source: String,
+    dest: String,
+    #[serde(default)]
+    mode: InsertMode,
+  },
+
+  Rename {
+    source: String,
+    name: String,
+  },
+
+  Remove {
+    source: String,
+  },
+
+  Set {
+    source: String,
+    icon: Option<String>,
+    status: Option<Status>,
+  },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TagCommand {
+  /// Add tags to a node.
+  ///
+  /// This command requires a base selection.
+  Add {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+
+    /// Tags to add, with or without a leading #.
+    #[arg(required = true)]
+    tags: Vec<String>,
+  },
+
+  /// Remove tags from a node.
+  ///
+  /// This command requires a base selection.
+  #[command(alias = "rm")]
+  Remove {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+
+    /// Tags to remove, with or without a leading #.
+    #[arg(required = true)]
+    tags: Vec<String>,
+  },
+
+  /// List tags.
+  ///
+  /// If a base node is selected, list its tags; otherwise, list all the tags used in the tree, with the number of
+  /// nodes using them.
+  #[command(name = "ls")]
+  List {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+  },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PropCommand {
+  /// Get properties of a node.
+  ///
+  /// If no key is provided, all the properties are printed as key=value.
+  ///
+  /// This command requires a base selection.
+  Get {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+
+    /// Key of the property to get.
+    key: Option<String>,
+  },
+
+  /// Set properties of a node.
+  ///
+  /// This command requires a base selection.
+  Set {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+
+    /// Properties to set, as key=value.
+    #[arg(required = true)]
+    properties: Vec<String>,
+  },
+
+  /// Remove properties from a node.
+  ///
+  /// This command requires a base selection.
+  Unset {
+    #[clap(flatten)]
+    common_args: CommonArgs,
+
+    /// Select a base node to operate on.
+    #[arg(short, long)]
+    source: Option<String>,
+
+    /// Keys of the properties to remove.
+    #[arg(required = true)]
+    keys: Vec<String>,
+  },
+}
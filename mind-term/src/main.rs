@@ -1,19 +1,38 @@
 mod cli;
 mod ui;
 
+use chrono::{DateTime, Local, NaiveDate, SecondsFormat, Utc};
 use clap::Parser;
-use cli::{Cli, Command, CommonArgs, DataArgs, InsertMode};
+use cli::{
+  BatchOperation, Cli, Command, CommonArgs, DataArgs, InfoArgs, InsertMode, MetaArgs, PropCommand,
+  SortBy, Status, TagCommand,
+};
 use colored::Colorize;
+use mind_tree::agenda::{agenda, AgendaSection};
 use mind_tree::config::Config;
 use mind_tree::data_file::{DataFileStore, DataFileStoreError};
+use mind_tree::date::{parse_date, DateError};
+use mind_tree::diff::{data_changes, diff, Change};
+use mind_tree::doctor::{
+  check_encoded_forest, check_encoded_tree, check_forest, check_tree, Issue,
+};
 use mind_tree::forest::{Forest, ForestError};
-use mind_tree::node::{path_iter, Node, NodeData, NodeError, NodeFilter};
+use mind_tree::history::Operation;
+use mind_tree::merge::{has_node_ids, merge};
+use mind_tree::node::{path_iter, Node, NodeData, NodeError, NodeFilter, NodeId, TaskStatus};
+use mind_tree::search::search;
+use mind_tree::stats::{forest_stats, tree_stats};
 use mind_tree::{encoding, node::Tree};
 use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::env::current_dir;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::{fs, io};
+use std::{
+  fs,
+  io::{self, IsTerminal},
+};
 use thiserror::Error;
 use ui::{UIError, UI};
 
@@ -93,6 +112,22 @@ impl App {
     Ok(Tree::from_encoding(tree))
   }
 
+  /// Load the local tree at the given path, if any.
+  ///
+  /// A tree that exists but cannot be loaded is an error, so that it’s never mistaken for a missing tree.
+  fn load_local_tree(path: impl AsRef<Path>) -> Result<Option<Tree>, PutainDeMerdeError> {
+    let path = path.as_ref();
+
+    match Self::load_tree(path) {
+      Ok(tree) => Ok(Some(tree)),
+      Err(PutainDeMerdeError::NoTreePersisted) => Ok(None),
+      Err(err) => Err(PutainDeMerdeError::CannotLoadLocalTree {
+        path: path.to_owned(),
+        err: Box::new(err),
+      }),
+    }
+  }
+
   fn persist_tree_to_path(tree: &Tree, path: impl AsRef<Path>) -> Result<(), PutainDeMerdeError> {
     let path = path.as_ref();
 
@@ -163,26 +198,140 @@ impl App {
         dest,
       } => self.run_move_cmd(common_args, *mode, source.as_deref(), dest.as_deref()),
 
+      Command::Clone {
+        common_args,
+        mode,
+        source,
+        dest,
+      } => self.run_clone_cmd(common_args, *mode, source.as_deref(), dest.as_deref()),
+
+      Command::Copy {
+        common_args,
+        mode,
+        source,
+        dest,
+        data_files,
+      } => self.run_copy_cmd(
+        common_args,
+        *mode,
+        source.as_deref(),
+        dest.as_deref(),
+        *data_files,
+      ),
+
       Command::Paths {
         common_args,
         file,
         uri,
+        tag,
+        prop,
+        query,
+        source,
+      } => {
+        let filter = match (tag, prop, query) {
+          (Some(tag), _, _) => NodeFilter::Tag(tag.to_owned()),
+          (_, Some(prop), _) => NodeFilter::property(prop),
+          (_, _, Some(query)) => NodeFilter::Query(query.clone()),
+          _ => NodeFilter::new(*file, *uri),
+        };
+
+        self.run_paths_cmd(common_args, filter, source.as_deref())
+      }
+
+      Command::Query {
+        common_args,
+        query,
         source,
-      } => self.run_paths_cmd(common_args, *file, *uri, source.as_deref()),
+      } => self.run_paths_cmd(
+        common_args,
+        NodeFilter::Query(query.clone()),
+        source.as_deref(),
+      ),
 
       Command::Get {
         common_args,
         file,
         uri,
         open,
+        attachment,
+        info_args,
         source,
-      } => self.run_get_cmd(common_args, *file, *uri, *open, source.as_deref()),
+      } => self.run_get_cmd(
+        common_args,
+        NodeFilter::new(*file, *uri),
+        *open,
+        *attachment,
+        info_args,
+        source.as_deref(),
+      ),
 
       Command::Set {
         common_args,
         data_args,
+        meta_args,
         source,
-      } => self.run_set_cmd(common_args, data_args, source.as_deref()),
+      } => self.run_set_cmd(common_args, data_args, meta_args, source.as_deref()),
+
+      Command::Tag { cmd } => self.run_tag_cmd(cmd),
+
+      Command::Prop { cmd } => self.run_prop_cmd(cmd),
+
+      Command::Backlinks {
+        common_args,
+        source,
+      } => self.run_backlinks_cmd(common_args, source.as_deref()),
+
+      Command::Status {
+        common_args,
+        source,
+        set,
+        cycle,
+      } => self.run_status_cmd(common_args, source.as_deref(), *set, *cycle),
+
+      Command::Sort {
+        common_args,
+        source,
+        by,
+        recursive,
+        reverse,
+      } => self.run_sort_cmd(common_args, source.as_deref(), *by, *recursive, *reverse),
+
+      Command::Recent {
+        common_args,
+        count,
+        time,
+        source,
+      } => self.run_recent_cmd(common_args, *count, *time, source.as_deref()),
+
+      Command::Agenda { days } => self.run_agenda_cmd(*days),
+
+      Command::Apply { common_args, file } => self.run_apply_cmd(common_args, file),
+
+      Command::Diff { old, new } => Self::run_diff_cmd(old, new),
+
+      Command::MergeFile {
+        base,
+        ours,
+        theirs,
+        output,
+      } => Self::run_merge_file_cmd(base, ours, theirs, output.as_deref()),
+
+      Command::Search { terms, limit } => self.run_search_cmd(terms, *limit),
+
+      Command::Gc {
+        dry_run,
+        delete,
+        trees,
+      } => self.run_gc_cmd(*dry_run, *delete, trees),
+
+      Command::Doctor { path, fix } => self.run_doctor_cmd(path.as_deref(), *fix),
+
+      Command::Stats {
+        common_args,
+        forest,
+        largest,
+        json,
+      } => self.run_stats_cmd(common_args, *forest, *largest, *json),
 
       Command::List {} => self.run_ls_cmd(),
 
@@ -211,7 +360,7 @@ impl App {
               .cwd_tree(cwd.clone())
               .cloned()
               .map(|tree| AppTree::Forest { forest, tree })
-              .ok_or_else(|| PutainDeMerdeError::NoCWDTree(cwd))
+              .ok_or(PutainDeMerdeError::NoCWDTree(cwd))
           } else {
             Ok(AppTree::Forest {
               tree: forest.main_tree().clone(),
@@ -223,6 +372,23 @@ impl App {
     }
   }
 
+  /// Select a node in a tree.
+  ///
+  /// The selection is either a path, e.g. `/a/b/c`, or a node ID prefixed with `id:`, e.g. `id:<ID>`, or `id:<ID>[N]` for
+  /// the N-th occurrence of a cloned node. Nodes are never created when selected by ID.
+  fn select_node(
+    tree: &Tree,
+    selection: &str,
+    auto_create_nodes: bool,
+  ) -> Result<Node, PutainDeMerdeError> {
+    let node = match selection.strip_prefix("id:") {
+      Some(id) => tree.select_node_by_id(id)?,
+      None => tree.get_node_by_path(path_iter(selection), auto_create_nodes)?,
+    };
+
+    node.ok_or(PutainDeMerdeError::MissingBaseSelection)
+  }
+
   /// Persist the application tree.
   fn persist(&self, tree: &AppTree) -> Result<(), PutainDeMerdeError> {
     match tree {
@@ -316,8 +482,10 @@ impl App {
           )
           .map(Cow::from)
       })
-      .and_then(|path| tree.get_node_by_path(path_iter(&path), self.config.tree.auto_create_nodes))
-      .ok_or(PutainDeMerdeError::MissingBaseSelection)?;
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| {
+        Self::select_node(&tree, &selection, self.config.tree.auto_create_nodes)
+      })?;
 
     let name = name
       .map(Cow::from)
@@ -344,8 +512,8 @@ impl App {
       InsertMode::After => source.insert_after(node.clone())?,
     }
 
-    if data_args.file || data_args.uri.is_some() {
-      self.check_create_open_data(common_args.interactive, data_args, &node)?;
+    if data_args.any() {
+      self.check_create_open_data(common_args.interactive, data_args, &tree, &node)?;
     }
 
     self.persist(&tree)
@@ -370,8 +538,8 @@ impl App {
           )
           .map(Cow::from)
       })
-      .and_then(|path| tree.get_node_by_path(path_iter(&path), false))
-      .ok_or(PutainDeMerdeError::MissingBaseSelection)?;
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| Self::select_node(&tree, &selection, false))?;
 
     let parent = source.parent()?;
     parent.delete(source)?;
@@ -399,8 +567,8 @@ impl App {
           )
           .map(Cow::from)
       })
-      .and_then(|path| tree.get_node_by_path(path_iter(&path), false))
-      .ok_or(PutainDeMerdeError::MissingBaseSelection)?;
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| Self::select_node(&tree, &selection, false))?;
 
     let name = new
       .map(Cow::from)
@@ -443,8 +611,10 @@ impl App {
           )
           .map(Cow::from)
       })
-      .and_then(|path| tree.get_node_by_path(path_iter(&path), self.config.tree.auto_create_nodes))
-      .ok_or(PutainDeMerdeError::MissingBaseSelection)?;
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| {
+        Self::select_node(&tree, &selection, self.config.tree.auto_create_nodes)
+      })?;
 
     let icon = icon
       .map(Cow::from)
@@ -484,8 +654,10 @@ impl App {
           )
           .map(Cow::from)
       })
-      .and_then(|path| tree.get_node_by_path(path_iter(&path), self.config.tree.auto_create_nodes))
-      .ok_or(PutainDeMerdeError::MissingBaseSelection)?;
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| {
+        Self::select_node(&tree, &selection, self.config.tree.auto_create_nodes)
+      })?;
 
     let dest = dest
       .map(Cow::from)
@@ -499,8 +671,10 @@ impl App {
           )
           .map(Cow::from)
       })
-      .and_then(|path| tree.get_node_by_path(path_iter(&path), self.config.tree.auto_create_nodes))
-      .ok_or(PutainDeMerdeError::MissingBaseSelection)?;
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| {
+        Self::select_node(&tree, &selection, self.config.tree.auto_create_nodes)
+      })?;
 
     match mode {
       InsertMode::InsideTop => dest.move_top(source)?,
@@ -512,16 +686,95 @@ impl App {
     self.persist(&tree)
   }
 
-  fn run_paths_cmd(
+  fn run_clone_cmd(
+    &self,
+    common_args: &CommonArgs,
+    mode: InsertMode,
+    source: Option<&str>,
+    dest: Option<&str>,
+  ) -> Result<(), PutainDeMerdeError> {
+    let tree = self.get_tree(common_args)?;
+    let source = self.select_source(common_args, &tree, source, "Source node: ")?;
+
+    let dest = dest
+      .map(Cow::from)
+      .or_else(|| {
+        self
+          .ui
+          .select_path(
+            ui::PickerOptions::either(common_args.interactive, "Destination node: "),
+            NodeFilter::default(),
+            &tree,
+          )
+          .map(Cow::from)
+      })
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| {
+        Self::select_node(&tree, &selection, self.config.tree.auto_create_nodes)
+      })?;
+
+    let clone = source.new_clone();
+    match mode {
+      InsertMode::InsideTop => dest.insert_top(clone),
+      InsertMode::InsideBottom => dest.insert_bottom(clone),
+      InsertMode::Before => dest.insert_before(clone)?,
+      InsertMode::After => dest.insert_after(clone)?,
+    }
+
+    self.persist(&tree)
+  }
+
+  fn run_copy_cmd(
     &self,
     common_args: &CommonArgs,
-    file: bool,
-    uri: bool,
+    mode: InsertMode,
     source: Option<&str>,
+    dest: Option<&str>,
+    data_files: bool,
   ) -> Result<(), PutainDeMerdeError> {
     let tree = self.get_tree(common_args)?;
-    let filter = NodeFilter::new(file, uri);
+    let source = self.select_source(common_args, &tree, source, "Source node: ")?;
 
+    let dest = dest
+      .map(Cow::from)
+      .or_else(|| {
+        self
+          .ui
+          .select_path(
+            ui::PickerOptions::either(common_args.interactive, "Destination node: "),
+            NodeFilter::default(),
+            &tree,
+          )
+          .map(Cow::from)
+      })
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| {
+        Self::select_node(&tree, &selection, self.config.tree.auto_create_nodes)
+      })?;
+
+    let copy = if data_files {
+      source.deep_copy_with_data_files(&self.data_file_store)?
+    } else {
+      source.deep_copy()
+    };
+
+    match mode {
+      InsertMode::InsideTop => dest.insert_top(copy),
+      InsertMode::InsideBottom => dest.insert_bottom(copy),
+      InsertMode::Before => dest.insert_before(copy)?,
+      InsertMode::After => dest.insert_after(copy)?,
+    }
+
+    self.persist(&tree)
+  }
+
+  fn run_paths_cmd(
+    &self,
+    common_args: &CommonArgs,
+    filter: NodeFilter,
+    source: Option<&str>,
+  ) -> Result<(), PutainDeMerdeError> {
+    let tree = self.get_tree(common_args)?;
     let prefix = source
       .map(Cow::from)
       .or_else(|| {
@@ -529,17 +782,15 @@ impl App {
           .ui
           .select_path(
             ui::PickerOptions::either(common_args.interactive, "Get paths: "),
-            filter,
+            filter.clone(),
             &tree,
           )
           .map(Cow::from)
       })
       .unwrap_or("/".into());
-    let source = tree
-      .get_node_by_path(path_iter(&prefix), false)
-      .ok_or(PutainDeMerdeError::MissingBaseSelection)?;
+    let source = Self::select_node(&tree, &prefix, false)?;
 
-    source.write_paths(&prefix, filter, &mut io::stdout())?;
+    source.write_paths(&source.path(), filter, &mut io::stdout())?;
 
     Ok(())
   }
@@ -547,13 +798,13 @@ impl App {
   fn run_get_cmd(
     &self,
     common_args: &CommonArgs,
-    file: bool,
-    uri: bool,
+    filter: NodeFilter,
     open: bool,
+    attachment: Option<usize>,
+    info_args: &InfoArgs,
     source: Option<&str>,
   ) -> Result<(), PutainDeMerdeError> {
     let tree = self.get_tree(common_args)?;
-    let filter = NodeFilter::new(file, uri);
 
     let source = source
       .map(Cow::from)
@@ -562,21 +813,317 @@ impl App {
           .ui
           .select_path(
             ui::PickerOptions::either(common_args.interactive, "Get data of: "),
-            filter,
+            filter.clone(),
             &tree,
           )
           .map(Cow::from)
       })
-      .and_then(|path| tree.get_node_by_path(path_iter(&path), self.config.tree.auto_create_nodes))
-      .ok_or(PutainDeMerdeError::MissingBaseSelection)?;
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| {
+        Self::select_node(&tree, &selection, self.config.tree.auto_create_nodes)
+      })?;
+
+    if info_args.any() {
+      Self::print_info(info_args, &source);
+      return Ok(());
+    }
 
-    self.get_open_data(open, &source)
+    let attachments = source
+      .attachments()
+      .into_iter()
+      .filter(|data| filter.accepts_attachment(data))
+      .collect();
+    self.get_open_data(
+      &tree,
+      common_args.interactive,
+      open,
+      attachment,
+      attachments,
+    )
+  }
+
+  /// Print the requested information about a node, one per line.
+  fn print_info(info_args: &InfoArgs, node: &Node) {
+    if info_args.id {
+      println!("{}", node.id());
+    }
+
+    if info_args.created {
+      println!("{}", node.created_at().map(format_time).unwrap_or_default());
+    }
+
+    if info_args.modified {
+      println!(
+        "{}",
+        node.modified_at().map(format_time).unwrap_or_default()
+      );
+    }
+
+    if info_args.due {
+      println!("{}", node.due().map(|d| d.to_string()).unwrap_or_default());
+    }
+
+    if info_args.scheduled {
+      println!(
+        "{}",
+        node.scheduled().map(|d| d.to_string()).unwrap_or_default()
+      );
+    }
+
+    if info_args.body {
+      println!("{}", node.body().unwrap_or_default());
+    }
+  }
+
+  fn run_tag_cmd(&self, cmd: &TagCommand) -> Result<(), PutainDeMerdeError> {
+    match cmd {
+      TagCommand::Add {
+        common_args,
+        source,
+        tags,
+      } => {
+        let tree = self.get_tree(common_args)?;
+        let source = self.select_source(common_args, &tree, source.as_deref(), "Tag: ")?;
+
+        for tag in tags {
+          source.add_tag(tag)?;
+        }
+
+        self.persist(&tree)
+      }
+
+      TagCommand::Remove {
+        common_args,
+        source,
+        tags,
+      } => {
+        let tree = self.get_tree(common_args)?;
+        let source = self.select_source(common_args, &tree, source.as_deref(), "Untag: ")?;
+
+        for tag in tags {
+          source.remove_tag(tag);
+        }
+
+        self.persist(&tree)
+      }
+
+      TagCommand::List {
+        common_args,
+        source,
+      } => {
+        let tree = self.get_tree(common_args)?;
+
+        // with no selection, list all the tags of the tree
+        if source.is_none() && !common_args.interactive {
+          let mut tags = BTreeMap::<String, usize>::new();
+          for node in tree.root().subtree() {
+            for tag in node.tags() {
+              *tags.entry(tag).or_default() += 1;
+            }
+          }
+
+          for (tag, count) in tags {
+            println!("#{} {}", tag, format!("({count})").dimmed());
+          }
+
+          return Ok(());
+        }
+
+        let source = self.select_source(common_args, &tree, source.as_deref(), "Tags of: ")?;
+        for tag in source.tags() {
+          println!("#{tag}");
+        }
+
+        Ok(())
+      }
+    }
+  }
+
+  fn run_prop_cmd(&self, cmd: &PropCommand) -> Result<(), PutainDeMerdeError> {
+    match cmd {
+      PropCommand::Get {
+        common_args,
+        source,
+        key,
+      } => {
+        let tree = self.get_tree(common_args)?;
+        let source =
+          self.select_source(common_args, &tree, source.as_deref(), "Properties of: ")?;
+
+        match key {
+          Some(key) => {
+            if let Some(value) = source.property(key) {
+              println!("{value}");
+            }
+          }
+
+          None => {
+            for (key, value) in source.properties() {
+              println!("{key}={value}");
+            }
+          }
+        }
+
+        Ok(())
+      }
+
+      PropCommand::Set {
+        common_args,
+        source,
+        properties,
+      } => {
+        let tree = self.get_tree(common_args)?;
+        let source =
+          self.select_source(common_args, &tree, source.as_deref(), "Set property of: ")?;
+
+        for property in properties {
+          let (key, value) = property
+            .split_once('=')
+            .ok_or_else(|| PutainDeMerdeError::InvalidProperty(property.to_owned()))?;
+          source.set_property(key, value)?;
+        }
+
+        self.persist(&tree)
+      }
+
+      PropCommand::Unset {
+        common_args,
+        source,
+        keys,
+      } => {
+        let tree = self.get_tree(common_args)?;
+        let source =
+          self.select_source(common_args, &tree, source.as_deref(), "Unset property of: ")?;
+
+        for key in keys {
+          source.unset_property(key);
+        }
+
+        self.persist(&tree)
+      }
+    }
+  }
+
+  fn run_status_cmd(
+    &self,
+    common_args: &CommonArgs,
+    source: Option<&str>,
+    set: Option<Status>,
+    cycle: bool,
+  ) -> Result<(), PutainDeMerdeError> {
+    let tree = self.get_tree(common_args)?;
+    let source = self.select_source(common_args, &tree, source, "Status of: ")?;
+
+    if let Some(status) = set {
+      source.set_status(status.task_status());
+      return self.persist(&tree);
+    }
+
+    if cycle {
+      source.cycle_status();
+      return self.persist(&tree);
+    }
+
+    let status = source.status().map(|status| {
+      let s = status.as_str();
+      match status {
+        TaskStatus::Todo => s.yellow(),
+        TaskStatus::Doing => s.blue(),
+        TaskStatus::Done => s.green(),
+        TaskStatus::Cancelled => s.dimmed(),
+      }
+    });
+    let progress = source.progress();
+
+    match (status, progress.total) {
+      (Some(status), 0) => println!("{status}"),
+      (Some(status), _) => println!("{status} [{progress}]"),
+      (None, 0) => (),
+      (None, _) => println!("[{progress}]"),
+    }
+
+    Ok(())
+  }
+
+  fn run_sort_cmd(
+    &self,
+    common_args: &CommonArgs,
+    source: Option<&str>,
+    by: SortBy,
+    recursive: bool,
+    reverse: bool,
+  ) -> Result<(), PutainDeMerdeError> {
+    let tree = self.get_tree(common_args)?;
+    let source = self.select_source(common_args, &tree, source, "Sort children of: ")?;
+    source.sort_children(by.sort_key(), reverse, recursive);
+    self.persist(&tree)
+  }
+
+  /// Select the node to operate on, either from the source argument or interactively.
+  fn select_source(
+    &self,
+    common_args: &CommonArgs,
+    tree: &Tree,
+    source: Option<&str>,
+    prompt: &'static str,
+  ) -> Result<Node, PutainDeMerdeError> {
+    source
+      .map(Cow::from)
+      .or_else(|| {
+        self
+          .ui
+          .select_path(
+            ui::PickerOptions::either(common_args.interactive, prompt),
+            NodeFilter::default(),
+            tree,
+          )
+          .map(Cow::from)
+      })
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| Self::select_node(tree, &selection, false))
+  }
+
+  fn run_recent_cmd(
+    &self,
+    common_args: &CommonArgs,
+    count: usize,
+    time: bool,
+    source: Option<&str>,
+  ) -> Result<(), PutainDeMerdeError> {
+    let tree = self.get_tree(common_args)?;
+
+    let source = source
+      .map(Cow::from)
+      .or_else(|| {
+        self
+          .ui
+          .select_path(
+            ui::PickerOptions::either(common_args.interactive, "Recent in: "),
+            NodeFilter::default(),
+            &tree,
+          )
+          .map(Cow::from)
+      })
+      .unwrap_or("/".into());
+    let source = Self::select_node(&tree, &source, false)?;
+
+    for node in source.recently_modified(count) {
+      if time {
+        let modified_at = node.modified_at().map(format_time).unwrap_or_default();
+        println!("{} {}", modified_at.dimmed(), node.path());
+      } else {
+        println!("{}", node.path());
+      }
+    }
+
+    Ok(())
   }
 
   fn run_set_cmd(
     &self,
     common_args: &CommonArgs,
     data_args: &DataArgs,
+    meta_args: &MetaArgs,
     source: Option<&str>,
   ) -> Result<(), PutainDeMerdeError> {
     let tree = self.get_tree(common_args)?;
@@ -593,10 +1140,45 @@ impl App {
           )
           .map(Cow::from)
       })
-      .and_then(|path| tree.get_node_by_path(path_iter(&path), self.config.tree.auto_create_nodes))
-      .ok_or(PutainDeMerdeError::MissingBaseSelection)?;
+      .ok_or(PutainDeMerdeError::MissingBaseSelection)
+      .and_then(|selection| {
+        Self::select_node(&tree, &selection, self.config.tree.auto_create_nodes)
+      })?;
+
+    // metadata can be set and attachments removed on their own, without any new data
+    let only_meta = meta_args.any() || data_args.detach.is_some();
+    if let Some(ref due) = meta_args.due {
+      source.set_due(parse_date_arg(due)?);
+    }
+
+    if let Some(ref scheduled) = meta_args.scheduled {
+      source.set_scheduled(parse_date_arg(scheduled)?);
+    }
+
+    if meta_args.body {
+      let body = if io::stdin().is_terminal() {
+        self.ui.edit_text(source.body().unwrap_or_default())?
+      } else {
+        io::read_to_string(io::stdin()).map_err(PutainDeMerdeError::CannotReadBody)?
+      };
+
+      source.set_body(Some(body));
+    }
+
+    if let Some(n) = data_args.detach {
+      let index = n
+        .checked_sub(1)
+        .ok_or(PutainDeMerdeError::NoSuchAttachment(n))?;
+      source.remove_attachment(index).map_err(|err| match err {
+        NodeError::NoSuchAttachment(_) => PutainDeMerdeError::NoSuchAttachment(n),
+        err => err.into(),
+      })?;
+    }
+
+    if !only_meta || data_args.any() {
+      self.check_create_open_data(common_args.interactive, data_args, &tree, &source)?;
+    }
 
-    self.check_create_open_data(common_args.interactive, data_args, &source)?;
     self.persist(&tree)
   }
 
@@ -605,10 +1187,18 @@ impl App {
     &self,
     interactive: bool,
     data_args: &DataArgs,
+    tree: &Tree,
     node: &Node,
   ) -> Result<(), PutainDeMerdeError> {
-    if let Some(NodeData::File(_)) = node.data() {
-      return Err(PutainDeMerdeError::DataAlreadyExists);
+    if let Some(ref reference) = data_args.reference {
+      let target = Self::select_node(tree, reference, false)?;
+      node.add_attachment(NodeData::node(target.id()))?;
+
+      if data_args.open {
+        self.open_data(tree, interactive, &NodeData::node(target.id()))?;
+      }
+
+      return Ok(());
     }
 
     let data = match (data_args.file, data_args.uri.as_ref().map(|o| o.as_deref())) {
@@ -639,35 +1229,621 @@ impl App {
       }
     };
 
-    // move inside above
-    node.set_data(data)?;
+    node.add_attachment(data.clone())?;
 
     if data_args.open {
-      self.get_open_data(data_args.open, node)?;
+      self.open_data(tree, interactive, &data)?;
     }
 
     Ok(())
   }
 
-  /// Get or open the data associated with a node.
-  fn get_open_data(&self, open: bool, node: &Node) -> Result<(), PutainDeMerdeError> {
-    if let Some(content) = node.data() {
-      match content {
-        NodeData::File(path) => {
-          if open {
-            self.ui.open_with_editor(path)?;
-          } else {
-            println!("{}", path.display())
-          }
+  /// Get or open attachments.
+  ///
+  /// All the attachments are printed, unless one is selected. Only one attachment can be opened; if there are several,
+  /// it must be selected, either by its position (starting at 1) or interactively.
+  fn get_open_data(
+    &self,
+    tree: &Tree,
+    interactive: bool,
+    open: bool,
+    attachment: Option<usize>,
+    mut attachments: Vec<NodeData>,
+  ) -> Result<(), PutainDeMerdeError> {
+    if let Some(n) = attachment {
+      let data = n
+        .checked_sub(1)
+        .and_then(|index| attachments.get(index).cloned())
+        .ok_or(PutainDeMerdeError::NoSuchAttachment(n))?;
+      attachments = vec![data];
+    }
+
+    if !open {
+      for data in attachments {
+        println!("{}", display_data(tree, &data));
+      }
+
+      return Ok(());
+    }
+
+    let data = match attachments.as_slice() {
+      [] => return Ok(()),
+      [data] => data,
+      _ => {
+        let picked = self
+          .ui
+          .select_item(
+            ui::PickerOptions::either(interactive, "Open: "),
+            attachments.iter().map(|data| display_data(tree, data)),
+          )
+          .ok_or(PutainDeMerdeError::AmbiguousAttachment)?;
+        attachments
+          .iter()
+          .find(|data| display_data(tree, data) == picked)
+          .ok_or(PutainDeMerdeError::AmbiguousAttachment)?
+      }
+    };
+
+    self.open_data(tree, interactive, data)
+  }
+
+  /// Open an attachment.
+  ///
+  /// “Opening” is contextual: files are edited with the editor and links are opened with the URI opener of the system.
+  /// Opening a node reference jumps to the referenced node: its files and links are opened instead (references are not
+  /// followed any further), or its path is printed if it has none.
+  fn open_data(
+    &self,
+    tree: &Tree,
+    interactive: bool,
+    data: &NodeData,
+  ) -> Result<(), PutainDeMerdeError> {
+    match data {
+      NodeData::File(path) => self.ui.open_with_editor(path)?,
+      NodeData::Link(link) => self.ui.open_uri(link)?,
+
+      NodeData::Node(id) => {
+        let target = tree
+          .get_node_by_id(*id)
+          .ok_or(PutainDeMerdeError::DanglingReference(*id))?;
+        let attachments: Vec<_> = target
+          .attachments()
+          .into_iter()
+          .filter(|data| !matches!(data, NodeData::Node(_)))
+          .collect();
+
+        if attachments.is_empty() {
+          println!("{}", target.path());
+        } else {
+          self.get_open_data(tree, interactive, true, None, attachments)?;
         }
+      }
+    }
 
-        NodeData::Link(link) => {
-          if open {
-            self.ui.open_uri(link)?;
-          } else {
-            println!("{}", link);
+    Ok(())
+  }
+
+  fn run_backlinks_cmd(
+    &self,
+    common_args: &CommonArgs,
+    source: Option<&str>,
+  ) -> Result<(), PutainDeMerdeError> {
+    let tree = self.get_tree(common_args)?;
+    let source = self.select_source(common_args, &tree, source, "Backlinks of: ")?;
+
+    for node in tree.backlinks(source.id()) {
+      println!("{}", node.path());
+    }
+
+    Ok(())
+  }
+
+  fn run_agenda_cmd(&self, days: u32) -> Result<(), PutainDeMerdeError> {
+    let forest = self.load_forest()?;
+    let today = Local::now().date_naive();
+
+    // nodes from CWD trees are prefixed with the directory of their tree
+    let mut items: Vec<_> = agenda(&forest.main_tree().root(), today, days)
+      .into_iter()
+      .map(|item| (String::new(), item))
+      .collect();
+    for (cwd, tree) in forest.cwd_trees() {
+      let prefix = format!("{}:", cwd.display());
+      items.extend(
+        agenda(&tree.root(), today, days)
+          .into_iter()
+          .map(|item| (prefix.clone(), item)),
+      );
+    }
+    items.sort_by_key(|(_, item)| (item.date, item.kind));
+
+    let sections = [
+      (AgendaSection::Overdue, "Overdue".bright_red()),
+      (AgendaSection::Today, "Today".bright_green()),
+      (AgendaSection::Upcoming, "Upcoming".bright_blue()),
+    ];
+
+    for (section, title) in sections {
+      let mut section_items = items
+        .iter()
+        .filter(|(_, item)| item.section(today) == section)
+        .peekable();
+
+      if section_items.peek().is_none() {
+        continue;
+      }
+
+      println!("{}", title.bold());
+      for (prefix, item) in section_items {
+        let status = item
+          .node
+          .status()
+          .map(|status| format!("{status} "))
+          .unwrap_or_default();
+        println!(
+          "  {} {:<9} {}{}{}",
+          item.date.to_string().dimmed(),
+          item.kind.as_str(),
+          status,
+          prefix.italic(),
+          item.node.path()
+        );
+      }
+    }
+
+    Ok(())
+  }
+
+  fn run_apply_cmd(&self, common_args: &CommonArgs, file: &Path) -> Result<(), PutainDeMerdeError> {
+    let content = if file == Path::new("-") {
+      io::read_to_string(io::stdin())
+    } else {
+      fs::read_to_string(file)
+    }
+    .map_err(PutainDeMerdeError::CannotReadOperations)?;
+    let operations: Vec<BatchOperation> =
+      serde_json::from_str(&content).map_err(PutainDeMerdeError::CannotDeserializeOperations)?;
+
+    let tree = self.get_tree(common_args)?;
+    let mut transaction = tree.transaction();
+
+    for (i, operation) in operations.into_iter().enumerate() {
+      Self::batch_operation(&tree, operation)
+        .and_then(|operation| Ok(transaction.apply(operation)?))
+        .map_err(|err| PutainDeMerdeError::BatchOperationFailed {
+          index: i + 1,
+          err: Box::new(err),
+        })?;
+    }
+
+    transaction.commit();
+    self.persist(&tree)
+  }
+
+  /// Resolve the nodes of a batch operation in the current state of the tree.
+  fn batch_operation(
+    tree: &Tree,
+    operation: BatchOperation,
+  ) -> Result<Operation, PutainDeMerdeError> {
+    let operation = match operation {
+      BatchOperation::Insert {
+        source,
+        name,
+        mode,
+        icon,
+      } => {
+        let source = Self::select_node(tree, &source, false)?;
+        let name = name.trim();
+        if name.is_empty() {
+          return Err(PutainDeMerdeError::EmptyName);
+        }
+
+        let node = Node::new(name, icon);
+        match mode {
+          InsertMode::InsideTop => Operation::insert_top(source, node),
+          InsertMode::InsideBottom => Operation::insert_bottom(source, node),
+          InsertMode::Before => Operation::insert_before(&source, node)?,
+          InsertMode::After => Operation::insert_after(&source, node)?,
+        }
+      }
+
+      BatchOperation::Move { source, dest, mode } => {
+        let source = Self::select_node(tree, &source, false)?;
+        let dest = Self::select_node(tree, &dest, false)?;
+
+        match mode {
+          InsertMode::InsideTop => Operation::move_top(dest, source),
+          InsertMode::InsideBottom => Operation::move_bottom(dest, source),
+          InsertMode::Before => Operation::move_before(&dest, source)?,
+          InsertMode::After => Operation::move_after(&dest, source)?,
+        }
+      }
+
+      BatchOperation::Rename { source, name } => Operation::Rename {
+        node: Self::select_node(tree, &source, false)?,
+        name,
+      },
+
+      BatchOperation::Remove { source } => Operation::Delete {
+        node: Self::select_node(tree, &source, false)?,
+      },
+
+      BatchOperation::Set {
+        source,
+        icon,
+        status,
+      } => {
+        let node = Self::select_node(tree, &source, false)?;
+        let icon = icon.map(|icon| Operation::SetIcon {
+          node: node.clone(),
+          icon,
+        });
+        let status = status.map(|status| Operation::SetStatus {
+          node: node.clone(),
+          status: status.task_status(),
+        });
+
+        Operation::Group(icon.into_iter().chain(status).collect())
+      }
+    };
+
+    Ok(operation)
+  }
+
+  fn run_diff_cmd(old: &Path, new: &Path) -> Result<(), PutainDeMerdeError> {
+    // nodes are matched by ID, so trees persisted before IDs existed would only show added and removed nodes
+    for path in [old, new] {
+      if !has_node_ids(&Self::load_json(path)?) {
+        return Err(PutainDeMerdeError::CannotDiffWithoutNodeIds(
+          path.to_owned(),
+        ));
+      }
+    }
+
+    let old = Self::load_tree(old)?;
+    let new = Self::load_tree(new)?;
+
+    for change in diff(&old, &new) {
+      match change {
+        Change::Added { new } => println!("{}", format!("+ {}", new.path()).green()),
+        Change::Removed { old } => println!("{}", format!("- {}", old.path()).red()),
+
+        Change::Renamed { old, new } => println!(
+          "{} {} {}",
+          "~".yellow(),
+          new.path().bold(),
+          format!("renamed from {}", old.name()).dimmed()
+        ),
+
+        Change::Moved { old, new } => println!(
+          "{} {} {}",
+          "~".yellow(),
+          new.path().bold(),
+          format!("moved from {}", old.path()).dimmed()
+        ),
+
+        Change::IconChanged { old, new } => println!(
+          "{} {} {}",
+          "~".yellow(),
+          new.path().bold(),
+          format!(
+            "icon changed from '{}' to '{}'",
+            old.icon().trim_end(),
+            new.icon().trim_end()
+          )
+          .dimmed()
+        ),
+
+        Change::DataChanged { old, new } => {
+          println!(
+            "{} {} {}",
+            "~".yellow(),
+            new.path().bold(),
+            "data changed".dimmed()
+          );
+
+          let (added, removed) = data_changes(&old, &new);
+          for data in removed {
+            println!("    {}", format!("- {data}").red());
           }
+
+          for data in added {
+            println!("    {}", format!("+ {data}").green());
+          }
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn run_merge_file_cmd(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    output: Option<&Path>,
+  ) -> Result<(), PutainDeMerdeError> {
+    // nodes are matched by ID, so trees persisted before IDs existed cannot be merged
+    for path in [base, ours, theirs] {
+      if !has_node_ids(&Self::load_json(path)?) {
+        return Err(PutainDeMerdeError::MissingNodeIds(path.to_owned()));
+      }
+    }
+
+    let merge = merge(
+      &Self::load_tree(base)?,
+      &Self::load_tree(ours)?,
+      &Self::load_tree(theirs)?,
+    );
+    Self::persist_tree_to_path(&merge.tree, output.unwrap_or(ours))?;
+
+    for conflict in &merge.conflicts {
+      eprintln!(
+        "{} {} {}",
+        "conflict:".red(),
+        conflict.node.path().bold(),
+        conflict.kind
+      );
+    }
+
+    if merge.conflicts.is_empty() {
+      Ok(())
+    } else {
+      Err(PutainDeMerdeError::MergeConflicts(merge.conflicts.len()))
+    }
+  }
+
+  fn run_search_cmd(&self, terms: &[String], limit: usize) -> Result<(), PutainDeMerdeError> {
+    let cwd = current_dir().map_err(PutainDeMerdeError::NoCWD)?;
+    let forest = self.load_forest()?;
+
+    // nodes from other trees than the main one are prefixed with their tree
+    let mut trees = vec![(String::new(), forest.main_tree().clone())];
+    for (cwd, tree) in forest.cwd_trees() {
+      trees.push((format!("{}:", cwd.display()), tree.clone()));
+    }
+    if let Some(tree) = Self::load_local_tree(Self::local_mind_path(&cwd))? {
+      trees.push(("local:".to_owned(), tree));
+    }
+
+    let mut hits: Vec<_> = trees
+      .iter()
+      .flat_map(|(prefix, tree)| {
+        search(&tree.root(), terms)
+          .into_iter()
+          .map(move |hit| (prefix, hit))
+      })
+      .collect();
+    hits.sort_by_key(|(_, hit)| Reverse(hit.score));
+
+    for (prefix, hit) in hits.into_iter().take(limit) {
+      println!("{}{}", prefix.italic(), hit.node.path().bold());
+
+      if let Some(snippet) = hit.snippet {
+        println!("  {}", snippet.dimmed());
+      }
+    }
+
+    Ok(())
+  }
+
+  fn run_gc_cmd(
+    &self,
+    dry_run: bool,
+    delete: bool,
+    paths: &[PathBuf],
+  ) -> Result<(), PutainDeMerdeError> {
+    let cwd = current_dir().map_err(PutainDeMerdeError::NoCWD)?;
+    let forest = self.load_forest()?;
+
+    let mut trees = vec![forest.main_tree().clone()];
+    let mut dirs = vec![cwd];
+    for (cwd, tree) in forest.cwd_trees() {
+      trees.push(tree.clone());
+      dirs.push(cwd.to_owned());
+    }
+    dirs.sort();
+    dirs.dedup();
+    // a local tree that cannot be loaded would make all of its data files look orphaned, so it aborts the collection
+    for dir in dirs {
+      if let Some(tree) = Self::load_local_tree(Self::local_mind_path(dir))? {
+        trees.push(tree);
+      }
+    }
+    for path in paths {
+      trees.push(Self::load_tree(path)?);
+    }
+
+    let orphans = self.data_file_store.orphaned_data_files(&trees)?;
+    if orphans.is_empty() {
+      println!("{}", "no orphaned data file".dimmed());
+      return Ok(());
+    }
+
+    for path in &orphans {
+      if dry_run {
+        println!("{}", path.display());
+      } else if delete {
+        fs::remove_file(path).map_err(DataFileStoreError::from)?;
+        println!("{} {}", "deleted".red(), path.display());
+      } else {
+        let trashed = self.data_file_store.trash(path)?;
+        println!(
+          "{} {} {}",
+          "trashed".yellow(),
+          path.display(),
+          format!("→ {}", trashed.display()).dimmed()
+        );
+      }
+    }
+
+    if dry_run {
+      println!(
+        "{}",
+        format!("{} orphaned data file(s)", orphans.len()).dimmed()
+      );
+    }
+
+    Ok(())
+  }
+
+  fn run_doctor_cmd(&self, path: Option<&Path>, fix: bool) -> Result<(), PutainDeMerdeError> {
+    // trees with an unknown version cannot be loaded, so they are checked first from their JSON representation
+    let version_issues = match path {
+      Some(path) => {
+        let value = Self::load_json(path)?;
+        check_encoded_tree(&value)
+          .map(|issue| (None, issue))
+          .into_iter()
+          .collect()
+      }
+
+      None => {
+        let path = self
+          .config
+          .persistence
+          .forest_path()
+          .ok_or(PutainDeMerdeError::NoForestPath)?;
+
+        if !path.exists() {
+          return Err(ForestError::NotPersisted(path).into());
         }
+
+        check_encoded_forest(&Self::load_json(path)?)
+      }
+    };
+
+    if !version_issues.is_empty() {
+      let (_, left) = Self::print_issues(&version_issues, false)?;
+      return Err(PutainDeMerdeError::IntegrityIssues(left));
+    }
+
+    let left = match path {
+      Some(path) => {
+        let tree = Self::load_tree(path)?;
+        let issues: Vec<_> = check_tree(&tree)
+          .into_iter()
+          .map(|issue| (None, issue))
+          .collect();
+        let (fixed, left) = Self::print_issues(&issues, fix)?;
+
+        if fixed > 0 {
+          Self::persist_tree_to_path(&tree, path)?;
+        }
+
+        left
+      }
+
+      None => {
+        let forest = self.load_forest()?;
+        let issues = check_forest(&forest);
+        let (fixed, left) = Self::print_issues(&issues, fix)?;
+
+        if fixed > 0 {
+          self.persist_forest(&forest)?;
+        }
+
+        left
+      }
+    };
+
+    if left > 0 {
+      return Err(PutainDeMerdeError::IntegrityIssues(left));
+    }
+
+    println!("{}", "✓ no issue left".bright_green());
+    Ok(())
+  }
+
+  /// Read a tree or a forest as raw JSON.
+  fn load_json(path: impl AsRef<Path>) -> Result<serde_json::Value, PutainDeMerdeError> {
+    let path = path.as_ref();
+
+    if !path.exists() {
+      return Err(PutainDeMerdeError::NoTreePersisted);
+    }
+
+    serde_json::from_str(&fs::read_to_string(path).map_err(PutainDeMerdeError::CannotReadTree)?)
+      .map_err(PutainDeMerdeError::CannotDeserializeTree)
+  }
+
+  /// Print integrity issues, prefixed with the directory of the CWD tree they are about, if any, and fix them if asked.
+  ///
+  /// Return the number of issues fixed and the number of issues left. Informational issues are printed as notes and
+  /// count as neither.
+  fn print_issues(
+    issues: &[(Option<PathBuf>, Issue)],
+    fix: bool,
+  ) -> Result<(usize, usize), PutainDeMerdeError> {
+    let mut fixed = 0;
+    let mut left = 0;
+
+    for (project, issue) in issues {
+      let prefix = project
+        .as_ref()
+        .map(|project| format!("{}: ", project.display()))
+        .unwrap_or_default();
+
+      // the issue is printed before being fixed, as fixing it might change the paths it refers to
+      let description = format!("{}{issue}", prefix.italic());
+      if issue.is_informational() {
+        println!("{} {description}", "note:".bright_blue());
+      } else if fix && issue.fix()? {
+        fixed += 1;
+        println!("{} {description}", "✓ fixed".bright_green());
+      } else {
+        left += 1;
+        println!("{} {description}", "✗".bright_red());
+      }
+    }
+
+    Ok((fixed, left))
+  }
+
+  fn run_stats_cmd(
+    &self,
+    common_args: &CommonArgs,
+    forest: bool,
+    largest: usize,
+    json: bool,
+  ) -> Result<(), PutainDeMerdeError> {
+    let stats = if forest {
+      forest_stats(&self.load_forest()?, largest)
+    } else {
+      let tree = self.get_tree(common_args)?;
+      tree_stats(&tree, largest)
+    };
+
+    if json {
+      let serialized =
+        serde_json::to_string_pretty(&stats).map_err(PutainDeMerdeError::CannotSerializeStats)?;
+      println!("{serialized}");
+      return Ok(());
+    }
+
+    let rows = [
+      ("nodes", stats.nodes.to_string()),
+      ("leaves", stats.leaves.to_string()),
+      ("max depth", stats.max_depth.to_string()),
+      ("average depth", format!("{:.2}", stats.average_depth)),
+      ("file nodes", stats.file_nodes.to_string()),
+      ("link nodes", stats.link_nodes.to_string()),
+      ("data files", format_size(stats.data_file_size)),
+    ];
+    for (label, value) in rows {
+      println!("{:<14}{}", label.dimmed(), value.bold());
+    }
+
+    if !stats.largest_subtrees.is_empty() {
+      println!("{}", "largest subtrees".dimmed());
+
+      let width = stats.largest_subtrees[0].descendants.to_string().len();
+      for subtree in &stats.largest_subtrees {
+        println!(
+          "  {:>width$} {}",
+          subtree.descendants.to_string().bold(),
+          subtree.path
+        );
       }
     }
 
@@ -743,6 +1919,51 @@ fn main() {
   App::bootstrap();
 }
 
+/// Display an attachment; node references are displayed as the path of the node they reference, if it still exists.
+fn display_data(tree: &Tree, data: &NodeData) -> String {
+  match data {
+    NodeData::Node(id) => tree
+      .get_node_by_id(*id)
+      .map(|node| node.path())
+      .unwrap_or_else(|| data.to_string()),
+    _ => data.to_string(),
+  }
+}
+
+/// Parse a date passed as argument; `none` clears the date.
+fn parse_date_arg(date: &str) -> Result<Option<NaiveDate>, PutainDeMerdeError> {
+  if date.trim().eq_ignore_ascii_case("none") {
+    return Ok(None);
+  }
+
+  Ok(Some(parse_date(date, Local::now().date_naive())?))
+}
+
+/// Format a date and time in the local timezone, as RFC 3339.
+fn format_time(time: DateTime<Utc>) -> String {
+  time
+    .with_timezone(&Local)
+    .to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// Format a size in bytes with binary units, e.g. `1.5 KiB`.
+fn format_size(size: u64) -> String {
+  const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+  if size < 1024 {
+    return format!("{size} B");
+  }
+
+  let mut size = size as f64 / 1024.;
+  let mut unit = 0;
+  while size >= 1024. && unit < UNITS.len() - 1 {
+    size /= 1024.;
+    unit += 1;
+  }
+
+  format!("{size:.1} {}", UNITS[unit])
+}
+
 #[derive(Debug, Error)]
 pub enum PutainDeMerdeError {
   #[error("missing a base node selection")]
@@ -787,21 +2008,69 @@ pub enum PutainDeMerdeError {
   #[error("no such CWD-based tree: {0}")]
   NoCWDTree(PathBuf),
 
+  #[error("cannot load local tree {}: {err}", path.display())]
+  CannotLoadLocalTree {
+    path: PathBuf,
+    err: Box<PutainDeMerdeError>,
+  },
+
   #[error("node with empty name")]
   EmptyName,
 
   #[error("node with no URI")]
   EmptyURI,
 
-  #[error("data already exists and cannot be replaced")]
-  DataAlreadyExists,
+  #[error("no attachment #{0}")]
+  NoSuchAttachment(usize),
+
+  #[error("referenced node {0} doesn’t exist anymore")]
+  DanglingReference(NodeId),
+
+  #[error("several attachments; select one with --attachment or --interactive")]
+  AmbiguousAttachment,
 
   #[error("cannot set both URI and file data on a node")]
   CannotSetURIAndfileData,
 
+  #[error("invalid property '{0}'; expected key=value")]
+  InvalidProperty(String),
+
+  #[error("cannot read body from the standard input: {0}")]
+  CannotReadBody(io::Error),
+
+  #[error("cannot read operations: {0}")]
+  CannotReadOperations(io::Error),
+
+  #[error("cannot deserialize operations: {0}")]
+  CannotDeserializeOperations(serde_json::Error),
+
+  #[error("operation #{index} failed, nothing was applied: {err}")]
+  BatchOperationFailed {
+    index: usize,
+    err: Box<PutainDeMerdeError>,
+  },
+
+  #[error("{0} conflict(s) left in the merged tree")]
+  MergeConflicts(usize),
+
+  #[error("cannot merge {}: some nodes have no ID, so the tree must be merged by hand", .0.display())]
+  MissingNodeIds(PathBuf),
+
+  #[error("cannot diff {}: some nodes have no ID, so they cannot be matched", .0.display())]
+  CannotDiffWithoutNodeIds(PathBuf),
+
+  #[error("{0} issue(s) left")]
+  IntegrityIssues(usize),
+
+  #[error("cannot serialize statistics: {0}")]
+  CannotSerializeStats(serde_json::Error),
+
   #[error("cannot write a path: {0}")]
   CannotWritePath(io::Error),
 
+  #[error("{0}")]
+  DateError(#[from] DateError),
+
   #[error("UI error: {0}")]
   UIError(#[from] UIError),
 
@@ -5,6 +5,7 @@ use mind_tree::{
   node::{NodeFilter, Tree},
 };
 use std::{
+  fs,
   io::{self, read_to_string, stdin, stdout, Write},
   path::Path,
   process::Stdio,
@@ -60,6 +61,39 @@ impl UI {
     })
   }
 
+  /// Select an item among several with the fuzzy program.
+  pub fn select_item(
+    &self,
+    picker_opts: PickerOptions,
+    items: impl IntoIterator<Item = String>,
+  ) -> Option<String> {
+    let PickerOptions::Interactive { prompt } = picker_opts else { return None; };
+    let program = self.fuzzy_term_program.as_ref()?;
+    let mut child = std::process::Command::new(program);
+    child.stdin(Stdio::piped()).stdout(Stdio::piped());
+
+    if let Some(ref prompt_prefix) = self.fuzzy_term_prompt_opt {
+      child.arg(format!("{} {}", prompt_prefix, prompt));
+    }
+
+    let child = child.spawn().ok()?;
+    let mut child_stdin = child.stdin?;
+    for item in items {
+      writeln!(child_stdin, "{item}").ok()?;
+    }
+    drop(child_stdin);
+
+    read_to_string(&mut child.stdout?).ok().and_then(|s| {
+      let s = s.trim();
+
+      if s.is_empty() {
+        None
+      } else {
+        Some(s.to_owned())
+      }
+    })
+  }
+
   pub fn input(&self, picker_opts: PickerOptions) -> Option<String> {
     let PickerOptions::Interactive { prompt } = picker_opts else { return None; };
 
@@ -81,6 +115,21 @@ impl UI {
       .ok_or(UIError::NoEditor)
   }
 
+  /// Edit some text with the editor, returning the edited text.
+  ///
+  /// The text is edited in a temporary file, removed afterwards.
+  pub fn edit_text(&self, text: impl AsRef<str>) -> Result<String, UIError> {
+    let path = std::env::temp_dir().join(format!("mind-{}.md", std::process::id()));
+    fs::write(&path, text.as_ref()).map_err(UIError::TextEdit)?;
+
+    let edited = self
+      .open_with_editor(&path)
+      .and_then(|_| fs::read_to_string(&path).map_err(UIError::TextEdit));
+    let _ = fs::remove_file(&path);
+
+    edited
+  }
+
   /// Open the editor at the given path.
   pub fn open_with_editor(&self, path: impl AsRef<Path>) -> Result<(), UIError> {
     let editor = self.get_editor()?;
@@ -111,6 +160,9 @@ pub enum UIError {
   #[error("error while editing: process returned {0}")]
   EditorError(io::Error),
 
+  #[error("cannot edit text: {0}")]
+  TextEdit(io::Error),
+
   #[error("error while opening URI {uri}: {err}")]
   URIError { uri: String, err: io::Error },
 }
@@ -0,0 +1,177 @@
+//! End-to-end tests of the `mind` command.
+
+use mind_tree::node::NodeId;
+use std::{
+  env, fs,
+  path::PathBuf,
+  process::{Command, Output},
+};
+
+/// Isolated environment, with its own home, data and configuration directories, and a tree.
+struct Env {
+  dir: PathBuf,
+  tree: PathBuf,
+}
+
+impl Env {
+  fn new() -> Self {
+    let dir = env::temp_dir().join(format!("mind-cli-{}", NodeId::new()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let env = Self {
+      tree: dir.join("tree.json"),
+      dir,
+    };
+    env.mind(&["init", "root"]);
+    env
+  }
+
+  /// Command running mind in the environment.
+  fn command(&self) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mind"));
+    command
+      .env("HOME", &self.dir)
+      .env("XDG_CONFIG_HOME", self.dir.join("config"))
+      .env("XDG_DATA_HOME", self.dir.join("data"));
+    command
+  }
+
+  fn run(&self, args: &[&str]) -> Output {
+    self
+      .command()
+      .args(&args[..1])
+      .arg("--path")
+      .arg(&self.tree)
+      .args(&args[1..])
+      .output()
+      .unwrap()
+  }
+
+  /// Run a command on the tree and return its standard output, failing if the command fails.
+  fn mind(&self, args: &[&str]) -> String {
+    let output = self.run(args);
+    assert!(
+      output.status.success(),
+      "mind {}: {}",
+      args.join(" "),
+      String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+  }
+
+  /// Run a command on the tree and return its standard error, failing if the command succeeds.
+  fn mind_err(&self, args: &[&str]) -> String {
+    let output = self.run(args);
+    assert!(
+      !output.status.success(),
+      "mind {} succeeded",
+      args.join(" ")
+    );
+    String::from_utf8(output.stderr).unwrap()
+  }
+}
+
+impl Drop for Env {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.dir);
+  }
+}
+
+#[test]
+fn insert_with_reference() {
+  let env = Env::new();
+  env.mind(&["insert", "-s", "/", "-n", "target"]);
+  env.mind(&["insert", "-s", "/", "-n", "see also", "--ref", "/target"]);
+
+  assert_eq!(env.mind(&["get", "-s", "/see also"]), "/target\n");
+  assert_eq!(env.mind(&["backlinks", "-s", "/target"]), "/see also\n");
+}
+
+#[test]
+fn set_reference_with_metadata() {
+  let env = Env::new();
+  env.mind(&["insert", "-s", "/", "-n", "target"]);
+  env.mind(&["insert", "-s", "/", "-n", "see also"]);
+  env.mind(&[
+    "set",
+    "-s",
+    "/see also",
+    "--ref",
+    "/target",
+    "--due",
+    "2030-01-01",
+  ]);
+
+  assert_eq!(env.mind(&["get", "-s", "/see also"]), "/target\n");
+  assert_eq!(
+    env.mind(&["get", "-s", "/see also", "--due"]),
+    "2030-01-01\n"
+  );
+}
+
+#[test]
+fn apply_rolls_back_on_failure() {
+  let env = Env::new();
+  env.mind(&["insert", "-s", "/", "-n", "a"]);
+
+  // inserting before the root fails, so the first operation must be rolled back
+  let operations = env.dir.join("operations.json");
+  fs::write(
+    &operations,
+    r#"[
+      {"op": "insert", "source": "/a", "name": "x"},
+      {"op": "insert", "source": "/", "name": "y", "mode": "before"}
+    ]"#,
+  )
+  .unwrap();
+
+  let err = env.mind_err(&["apply", operations.to_str().unwrap()]);
+  assert!(err.contains("operation #2 failed"), "{err}");
+  assert_eq!(env.mind(&["paths"]), "/\n/a\n");
+}
+
+#[test]
+fn merge_file_without_node_ids() {
+  let env = Env::new();
+  let v1 = include_str!("../../mind/fixtures/v1/tree.json");
+  let [base, ours, theirs] =
+    ["base", "ours", "theirs"].map(|name| env.dir.join(format!("{name}.json")));
+  for path in [&base, &ours, &theirs] {
+    fs::write(path, v1).unwrap();
+  }
+
+  let output = env
+    .command()
+    .arg("merge-file")
+    .args([&base, &ours, &theirs])
+    .output()
+    .unwrap();
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("some nodes have no ID"));
+  assert_eq!(fs::read_to_string(ours).unwrap(), v1);
+}
+
+#[test]
+fn doctor_notes_duplicate_names() {
+  let env = Env::new();
+  env.mind(&["insert", "-s", "/", "-n", "y"]);
+  env.mind(&["insert", "-s", "/", "-n", "y"]);
+
+  // siblings sharing a name are not an integrity issue, so they are neither failing nor fixed
+  let out = env.mind(&["doctor", "--fix"]);
+  assert!(out.contains("several children are named 'y'"), "{out}");
+  assert!(out.contains("no issue left"), "{out}");
+  assert_eq!(env.mind(&["paths"]), "/\n/y[1]\n/y[2]\n");
+}
+
+#[test]
+fn diff_without_node_ids() {
+  let env = Env::new();
+  let v1 = env.dir.join("v1.json");
+  fs::write(&v1, include_str!("../../mind/fixtures/v1/tree.json")).unwrap();
+
+  let output = env.command().arg("diff").args([&v1, &v1]).output().unwrap();
+  assert!(!output.status.success());
+  assert!(String::from_utf8_lossy(&output.stderr).contains("some nodes have no ID"));
+  assert!(output.stdout.is_empty());
+}
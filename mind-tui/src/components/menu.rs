@@ -114,7 +114,7 @@ impl RawEventHandler for Menu {
   }
 }
 
-impl<'a> Widget for &'a Menu {
+impl Widget for &Menu {
   fn render(self, mut area: Rect, buf: &mut Buffer) {
     // render the title and increment the area to render the items below
     buf.set_string(
@@ -225,7 +225,7 @@ impl TuiMenu {
   }
 }
 
-impl<'a> Widget for &'a TuiMenu {
+impl Widget for &TuiMenu {
   fn render(self, area: Rect, buf: &mut Buffer) {
     if let Some((ref menu, _)) = self.menu {
       menu.render(area, buf);
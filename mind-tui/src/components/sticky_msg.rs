@@ -17,7 +17,7 @@ impl StickyMsg {
     }
   }
 
-  pub fn span(&self) -> &Span {
+  pub fn span(&self) -> &Span<'static> {
     &self.span
   }
 
@@ -1,7 +1,7 @@
 use std::sync::mpsc::{channel, Receiver, Sender};
 
-use crossterm::event::{KeyCode, KeyEvent};
-use mind_tree::node::{Cursor, Node, NodeData};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use mind_tree::node::{Cursor, Node, NodeData, SubtreeProgress, TaskStatus};
 use tui::{
   buffer::Buffer,
   layout::Rect,
@@ -14,6 +14,7 @@ use crate::{
   error::AppError,
   event::{Event, HandledEvent, RawEventHandler},
   ops::InsertMode,
+  req::UserCmd,
 };
 
 use super::user_input::UserInputPrompt;
@@ -112,13 +113,53 @@ impl TuiTree {
   }
 
   fn adjust_view(&mut self) {
-    let y = self.selected_node_id as isize - self.top_shift as isize;
+    let selected = self.selected_node_id;
+    let mut top = self.top_shift as usize;
 
-    if y < 0 {
-      self.top_shift -= -y as u16;
-    } else if y >= self.rect.height as isize {
-      self.top_shift += 1 + y as u16 - self.rect.height;
+    if selected < top {
+      self.top_shift = selected as u16;
+      return;
     }
+
+    // nodes can take several lines because of their body, so we need the lines of the nodes from the top of the view
+    // down to the selected one
+    let mut heights = Vec::new();
+    visible_heights(&self.root, &mut heights);
+
+    let rows_to_selected = |top: usize| {
+      heights
+        .get(top..selected)
+        .map_or(0, |h| h.iter().sum::<usize>())
+        + 1
+    };
+    while top < selected && rows_to_selected(top) > self.rect.height as usize {
+      top += 1;
+    }
+
+    self.top_shift = top as u16;
+  }
+
+  /// Currently selected node.
+  pub fn selected_node(&self) -> Node {
+    self.cursor.node().clone()
+  }
+
+  /// Select a node, expanding its ancestors so that it’s visible.
+  pub fn select_node(&mut self, node: &Node) {
+    let mut parent = node.parent();
+    while let Ok(node) = parent {
+      node.set_expanded(true);
+      parent = node.parent();
+    }
+
+    // the selected node ID is the visual line of the node, so we have to walk the tree from the top
+    self.cursor = Cursor::new(self.root.clone());
+    self.selected_node_id = 0;
+    while !self.cursor.points_to(node) && self.cursor.visual_next() {
+      self.selected_node_id += 1;
+    }
+
+    self.adjust_view();
   }
 
   fn open_prompt_insert_node(&mut self, title: &str, mode: InsertMode) {
@@ -159,7 +200,7 @@ impl TuiTree {
   }
 }
 
-impl<'a> Widget for &'a TuiTree {
+impl Widget for &TuiTree {
   fn render(self, mut area: Rect, buf: &mut Buffer) {
     if let Some(prompt) = self.input_prompt.prompt() {
       prompt.render(
@@ -188,6 +229,7 @@ impl<'a> Widget for &'a TuiTree {
       &Indent::default(),
       false,
       &self.cursor,
+      &SubtreeProgress::new(&self.root),
     );
   }
 }
@@ -224,6 +266,15 @@ impl RawEventHandler for TuiTree {
         self.adjust_view();
       }
 
+      crossterm::event::Event::Key(KeyEvent {
+        code: KeyCode::Char('r'),
+        modifiers,
+        ..
+      }) if modifiers.contains(KeyModifiers::CONTROL) => {
+        self.emit_event(Event::Command(UserCmd::Redo))?;
+        return Ok((HandledEvent::handled(), ()));
+      }
+
       crossterm::event::Event::Key(KeyEvent { code, .. }) => match code {
         KeyCode::Char('t') => {
           self.select_next_node();
@@ -265,6 +316,48 @@ impl RawEventHandler for TuiTree {
           return Ok((HandledEvent::handled(), ()));
         }
 
+        KeyCode::Char('u') => {
+          self.emit_event(Event::Command(UserCmd::Undo))?;
+          return Ok((HandledEvent::handled(), ()));
+        }
+
+        KeyCode::Char('x') => {
+          self.emit_event(Event::CycleNodeStatus {
+            id: self.selected_node_id,
+          })?;
+          return Ok((HandledEvent::handled(), ()));
+        }
+
+        KeyCode::Char('y') => {
+          self.emit_event(Event::YankNode {
+            id: self.selected_node_id,
+          })?;
+          return Ok((HandledEvent::handled(), ()));
+        }
+
+        KeyCode::Char('p') => {
+          self.emit_event(Event::PasteNode {
+            id: self.selected_node_id,
+            mode: InsertMode::After,
+          })?;
+          return Ok((HandledEvent::handled(), ()));
+        }
+
+        KeyCode::Char('P') => {
+          self.emit_event(Event::PasteNode {
+            id: self.selected_node_id,
+            mode: InsertMode::Before,
+          })?;
+          return Ok((HandledEvent::handled(), ()));
+        }
+
+        KeyCode::Char('a') => {
+          self.emit_event(Event::AttachNodeData {
+            id: self.selected_node_id,
+          })?;
+          return Ok((HandledEvent::handled(), ()));
+        }
+
         // ask to the node; the workflow requires to first emit an event so that the logic checks whether we should
         // open the data directly (if present), or open a menu to ask which kind of data to add
         KeyCode::Enter => {
@@ -341,8 +434,25 @@ impl Indent {
   }
 }
 
+/// Body of a node, if it’s displayed below it.
+fn shown_body(node: &Node) -> Option<String> {
+  node.body().filter(|_| node.is_expanded())
+}
+
+/// Number of lines taken by each visible node, in visual order: the node itself and its body, if displayed.
+fn visible_heights(node: &Node, heights: &mut Vec<usize>) {
+  heights.push(1 + shown_body(node).map_or(0, |body| body.lines().count()));
+
+  if node.is_expanded() {
+    for child in node.children().into_iter() {
+      visible_heights(child, heights);
+    }
+  }
+}
+
 /// Render the node in the given area with the given indent level, and its children.
 /// Abort before rendering outside of the area (Y axis).
+#[allow(clippy::too_many_arguments)]
 pub fn render_with_indent(
   node: &Node,
   top_shift: u16,
@@ -352,6 +462,7 @@ pub fn render_with_indent(
   indent: &Indent,
   is_last: bool,
   cursor: &Cursor,
+  progress: &SubtreeProgress,
 ) -> Option<(Rect, u16)> {
   if id >= top_shift {
     // indent guides
@@ -378,13 +489,29 @@ pub fn render_with_indent(
     buf.set_string(render_x, area.y, &icon.content, icon.style);
     render_x += icon.width() as u16;
 
+    // task status
+    if let Some(status) = node.status() {
+      let color = match status {
+        TaskStatus::Todo => Color::Yellow,
+        TaskStatus::Doing => Color::Blue,
+        TaskStatus::Done => Color::Green,
+        TaskStatus::Cancelled => Color::DarkGray,
+      };
+      let status = Span::styled(
+        format!("{} ", status),
+        Style::default().fg(color).add_modifier(Modifier::BOLD),
+      );
+      buf.set_string(render_x, area.y, &status.content, status.style);
+      render_x += status.width() as u16;
+    }
+
     let cursor_start_x = render_x;
 
     // content rendering
     let text_style = Style::default();
     let text_style = if node.has_children() {
       text_style.add_modifier(Modifier::BOLD)
-    } else if node.data().is_some() {
+    } else if node.has_attachments() {
       text_style.fg(Color::Magenta)
     } else {
       text_style
@@ -395,19 +522,33 @@ pub fn render_with_indent(
     render_x += text.width() as u16;
     let cursor_end_x = render_x;
 
-    // markers; used mainly for data nodes
-    match node.data() {
-      Some(NodeData::File(_)) => {
-        let marker = Span::styled("  ", Style::default().fg(Color::Black));
-        buf.set_string(render_x, area.y, &marker.content, marker.style);
-      }
+    // progress of the tasks below, if any
+    let node_progress = progress.get(node);
+    if node_progress.total > 0 {
+      let progress = Span::styled(
+        format!(" [{node_progress}]"),
+        Style::default().fg(Color::DarkGray),
+      );
+      buf.set_string(render_x, area.y, &progress.content, progress.style);
+      render_x += progress.width() as u16;
+    }
 
-      Some(NodeData::Link(_)) => {
-        let marker = Span::styled("  ", Style::default().fg(Color::Black));
-        buf.set_string(render_x, area.y, &marker.content, marker.style);
-      }
+    // clone marker, if the node also appears somewhere else
+    if node.is_cloned() {
+      let marker = Span::styled(" ⧉", Style::default().fg(Color::Cyan));
+      buf.set_string(render_x, area.y, &marker.content, marker.style);
+      render_x += marker.width() as u16;
+    }
 
-      _ => (),
+    // markers; one per attachment
+    for data in node.attachments() {
+      let marker = match data {
+        NodeData::File(_) => Span::styled("  ", Style::default().fg(Color::Black)),
+        NodeData::Link(_) => Span::styled("  ", Style::default().fg(Color::Black)),
+        NodeData::Node(_) => Span::styled(" ↪ ", Style::default().fg(Color::Black)),
+      };
+      buf.set_string(render_x, area.y, &marker.content, marker.style);
+      render_x += marker.width() as u16;
     }
 
     if cursor.points_to(node) {
@@ -416,6 +557,37 @@ pub fn render_with_indent(
         Style::default().add_modifier(Modifier::REVERSED),
       );
     }
+
+    // body text, below the node when expanded
+    if let Some(body) = shown_body(node) {
+      let mut body_guides: String = indent.signs.iter().flat_map(|sign| [*sign, ' ']).collect();
+      body_guides.push_str(if node.has_children() { "│ " } else { "  " });
+      let body_x = body_guides.chars().count() as u16;
+
+      for line in body.lines() {
+        area.y += 1;
+
+        // abort if we are at the bottom of the area
+        if area.y >= area.height {
+          return None;
+        }
+
+        buf.set_string(
+          area.x,
+          area.y,
+          &body_guides,
+          Style::default().fg(Color::Black),
+        );
+        buf.set_string(
+          body_x,
+          area.y,
+          line,
+          Style::default()
+            .fg(Color::DarkGray)
+            .add_modifier(Modifier::ITALIC),
+        );
+      }
+    }
   }
 
   // nothing else to do if we don’t have any children or they are collapsed
@@ -445,6 +617,7 @@ pub fn render_with_indent(
       &new_indent,
       false,
       cursor,
+      progress,
     )?;
 
     area = new_area;
@@ -472,6 +645,7 @@ pub fn render_with_indent(
       &new_indent,
       true,
       cursor,
+      progress,
     )?;
 
     area = new_area;
@@ -274,14 +274,37 @@ impl Tui {
             log::debug!("adapting TUI to renamed node…");
           }
 
+          Request::ChangedNodeStatus { .. } => {
+            log::debug!("adapting TUI to node status change…");
+          }
+
           Request::PromptNodeData { sender } => {
             self.open_menu(
               "create data",
-              [MenuItem::new("file", 'f'), MenuItem::new("url", 'u')],
+              [
+                MenuItem::new("file", 'f'),
+                MenuItem::new("url", 'u'),
+                MenuItem::new("node", 'n'),
+              ],
               sender,
             );
           }
 
+          Request::PromptNodeAttachment {
+            attachments,
+            sender,
+          } => {
+            self.open_menu("open attachment", numbered_items(attachments), sender);
+          }
+
+          Request::PromptQueryResult { paths, sender } => {
+            self.open_menu("jump to node", numbered_items(paths), sender);
+          }
+
+          Request::JumpToNode { node } => self.tree.select_node(&node),
+
+          Request::GetSelectedNode { sender } => sender.send(self.tree.selected_node()).unwrap(),
+
           Request::UserInput { title, sender } => self.open_prompt(title, sender),
 
           Request::OpenEditor { path } => {
@@ -333,3 +356,15 @@ impl RawEventHandler for Tui {
     Ok((handled, ()))
   }
 }
+
+/// Menu items which can be picked directly with a digit, for the first ones.
+fn numbered_items(names: Vec<String>) -> Vec<MenuItem> {
+  names
+    .into_iter()
+    .enumerate()
+    .map(|(i, name)| {
+      let key = char::from_digit(i as u32 + 1, 10).filter(|_| i < 9);
+      MenuItem::new(name, key)
+    })
+    .collect()
+}
@@ -188,7 +188,7 @@ impl InputPrompt {
   }
 }
 
-impl<'a> Widget for &'a InputPrompt {
+impl Widget for &InputPrompt {
   fn render(self, area: Rect, buf: &mut Buffer) {
     // render the prefix grey with no text; green if the function is valid and red if not
     let input_str = self.input.as_str();
@@ -29,7 +29,7 @@ pub enum AppError {
   Event(String),
 
   #[error("error while sending a request to the TUI: {0}")]
-  Request(#[from] SendError<Request>),
+  Request(#[from] Box<SendError<Request>>),
 
   #[error("rendering error: {0}")]
   Render(std::io::Error),
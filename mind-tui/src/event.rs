@@ -64,8 +64,20 @@ pub enum Event {
   /// Node data open.
   OpenNodeData { id: usize },
 
+  /// Attach new data to a node.
+  AttachNodeData { id: usize },
+
   /// Rename a node.
   RenameNode { id: usize, rename: String },
+
+  /// Cycle the task status of a node.
+  CycleNodeStatus { id: usize },
+
+  /// Yank a copy of a node and its children, to paste it later.
+  YankNode { id: usize },
+
+  /// Paste the last yanked node.
+  PasteNode { id: usize, mode: InsertMode },
 }
 
 impl Event {
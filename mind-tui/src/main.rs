@@ -14,7 +14,10 @@ use mind_tree::{
   config::Config,
   data_file::DataFileStore,
   forest::Forest,
-  node::{Node, NodeData},
+  history::Operation,
+  node::{
+    path_iter, query::Query, Node, NodeData, NodeError, NodeFilter, NodeId, SortKey, TaskStatus,
+  },
 };
 use ops::InsertMode;
 use req::{Request, UserCmd};
@@ -29,6 +32,9 @@ use std::{
 };
 use tui::layout::Rect;
 
+/// Maximum number of nodes matching a query that are shown to the user.
+const MAX_QUERY_RESULTS: usize = 20;
+
 fn main() {
   if let Err(err) = bootstrap() {
     eprintln!("{}", err);
@@ -48,6 +54,9 @@ struct App {
   forest: Forest,
   data_file_store: DataFileStore,
   dirty: bool,
+
+  /// Copy of the last yanked node, if any.
+  yanked: Option<Node>,
 }
 
 #[derive(Debug)]
@@ -104,7 +113,7 @@ impl App {
           format!("error while reading configuration: {}", config_err),
           Duration::from_secs(5),
         ))
-        .map_err(AppError::Request)?;
+        .map_err(|err| AppError::Request(Box::new(err)))?;
     }
 
     let data_file_store = DataFileStore::new(
@@ -123,6 +132,7 @@ impl App {
       forest,
       data_file_store,
       dirty,
+      yanked: None,
     })
   }
 
@@ -155,7 +165,11 @@ impl App {
 
   /// Send a request to the TUI.
   fn request(&self, req: Request) -> Result<(), AppError> {
-    self.tui_data.request_sx.send(req).map_err(AppError::from)
+    self
+      .tui_data
+      .request_sx
+      .send(req)
+      .map_err(|err| AppError::from(Box::new(err)))
   }
 
   /// Wait and dispatch incoming events from the TUI.
@@ -168,7 +182,11 @@ impl App {
         Event::InsertNode { id, mode, name } => self.on_insert_node(id, mode, name)?,
         Event::DeleteNode { id } => self.on_delete_node(id)?,
         Event::OpenNodeData { id } => self.on_open_node_data(id)?,
+        Event::AttachNodeData { id } => self.on_attach_node_data(id)?,
         Event::RenameNode { id, rename } => self.on_rename_node(id, rename)?,
+        Event::CycleNodeStatus { id } => self.on_cycle_node_status(id)?,
+        Event::YankNode { id } => self.on_yank_node(id)?,
+        Event::PasteNode { id, mode } => self.on_paste_node(id, mode)?,
       }
     }
 
@@ -203,6 +221,97 @@ impl App {
         self.dirty = false;
         self.request(Request::info_msg("state saved"))?;
       }
+
+      UserCmd::Query(query) => self.on_query(&query)?,
+
+      UserCmd::Undo => {
+        let undone = self.forest.main_tree().undo();
+        self.on_history_change(undone, "nothing to undo")?;
+      }
+
+      UserCmd::Redo => {
+        let redone = self.forest.main_tree().redo();
+        self.on_history_change(redone, "nothing to redo")?;
+      }
+
+      UserCmd::Sort {
+        key,
+        reverse,
+        recursive,
+      } => self.on_sort(key, reverse, recursive)?,
+    }
+
+    Ok(())
+  }
+
+  /// Move to the node affected by an undo or a redo.
+  fn on_history_change(
+    &mut self,
+    target: Result<Option<Node>, NodeError>,
+    nothing_msg: &str,
+  ) -> Result<(), AppError> {
+    match target {
+      Ok(Some(node)) => {
+        self.dirty = true;
+        self.request(Request::JumpToNode { node })
+      }
+
+      Ok(None) => self.request(Request::info_msg(nothing_msg)),
+      Err(err) => self.request(Request::err_msg(err.to_string())),
+    }
+  }
+
+  /// Sort the children of the selected node.
+  fn on_sort(&mut self, key: SortKey, reverse: bool, recursive: bool) -> Result<(), AppError> {
+    let (sender, rx) = channel();
+    self.request(Request::GetSelectedNode { sender })?;
+
+    let Ok(node) = rx.recv() else {
+      return Ok(());
+    };
+
+    self.forest.main_tree().apply(Operation::Sort {
+      node: node.clone(),
+      key,
+      reverse,
+      recursive,
+    })?;
+    self.dirty = true;
+
+    // refresh the view while keeping the node selected
+    self.request(Request::JumpToNode { node })
+  }
+
+  /// Find the nodes matching a query, and let the user pick one to jump to.
+  fn on_query(&mut self, query: &str) -> Result<(), AppError> {
+    let query = match query.parse::<Query>() {
+      Ok(query) => query,
+      Err(err) => return self.request(Request::err_msg(err.to_string())),
+    };
+
+    let tree = self.forest.main_tree();
+    let mut paths = tree.root().paths("/", NodeFilter::Query(query));
+
+    if paths.is_empty() {
+      return self.request(Request::info_msg("no node matches the query"));
+    }
+
+    if paths.len() > MAX_QUERY_RESULTS {
+      self.request(Request::warn_msg(format!(
+        "{} nodes match the query; only the first {MAX_QUERY_RESULTS} are shown",
+        paths.len()
+      )))?;
+      paths.truncate(MAX_QUERY_RESULTS);
+    }
+
+    let (sender, rx) = channel();
+    self.request(Request::PromptQueryResult { paths, sender })?;
+
+    // wait for the TUI to reply with the node to jump to
+    if let Ok(Some(item)) = rx.recv() {
+      if let Ok(Some(node)) = tree.get_node_by_path(path_iter(&item.name), false) {
+        self.request(Request::JumpToNode { node })?;
+      }
     }
 
     Ok(())
@@ -219,15 +328,17 @@ impl App {
   fn on_insert_node(&mut self, id: usize, mode: InsertMode, name: String) -> Result<(), AppError> {
     log::info!("inserting node {id} {name}: {mode:?}");
 
-    if let Some(anchor) = self.forest.main_tree().get_node_by_line(id) {
+    let tree = self.forest.main_tree();
+    if let Some(anchor) = tree.get_node_by_line(id) {
       let node = Node::new(name, "");
-      match mode {
-        InsertMode::InsideTop => anchor.insert_top(node),
-        InsertMode::InsideBottom => anchor.insert_bottom(node),
-        InsertMode::Before => anchor.insert_before(node)?,
-        InsertMode::After => anchor.insert_after(node)?,
-      }
-
+      let operation = match mode {
+        InsertMode::InsideTop => Operation::insert_top(anchor, node),
+        InsertMode::InsideBottom => Operation::insert_bottom(anchor, node),
+        InsertMode::Before => Operation::insert_before(&anchor, node)?,
+        InsertMode::After => Operation::insert_after(&anchor, node)?,
+      };
+
+      tree.apply(operation)?;
       self.dirty = true;
       self.request(Request::InsertedNode { id, mode })?;
     }
@@ -238,9 +349,10 @@ impl App {
   fn on_delete_node(&mut self, id: usize) -> Result<(), AppError> {
     log::info!("deleting node {id}");
 
-    if let Some(node) = self.forest.main_tree().get_node_by_line(id) {
-      if let Ok(parent) = node.parent() {
-        parent.delete(node)?;
+    let tree = self.forest.main_tree();
+    if let Some(node) = tree.get_node_by_line(id) {
+      if node.parent().is_ok() {
+        tree.apply(Operation::Delete { node })?;
         self.dirty = true;
         self.request(Request::DeletedNode { id })?;
       } else {
@@ -253,19 +365,65 @@ impl App {
 
   fn on_open_node_data(&mut self, id: usize) -> Result<(), AppError> {
     if let Some(node) = self.forest.main_tree().get_node_by_line(id) {
-      match node.data() {
-        Some(data) => self.open_node_data(&data)?,
-        None => self.request_prompt_node_data(node)?,
+      match node.attachments().as_slice() {
+        [] => self.request_prompt_node_data(node)?,
+        [data] => self.open_node_data(data)?,
+        attachments => self.request_prompt_node_attachment(attachments)?,
       }
     }
 
     Ok(())
   }
 
+  fn on_attach_node_data(&mut self, id: usize) -> Result<(), AppError> {
+    if let Some(node) = self.forest.main_tree().get_node_by_line(id) {
+      self.request_prompt_node_data(node)?;
+    }
+
+    Ok(())
+  }
+
+  fn request_prompt_node_attachment(&mut self, attachments: &[NodeData]) -> Result<(), AppError> {
+    let (sender, rx) = channel();
+    self.request(Request::PromptNodeAttachment {
+      attachments: attachments
+        .iter()
+        .map(|data| self.display_node_data(data))
+        .collect(),
+      sender,
+    })?;
+
+    // wait for the TUI to reply with the attachment to open
+    if let Ok(Some(item)) = rx.recv() {
+      if let Some(data) = attachments
+        .iter()
+        .find(|data| self.display_node_data(data) == item.name)
+      {
+        self.open_node_data(data)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Display node data; node references are displayed as the path of the node they reference, if it still exists.
+  fn display_node_data(&self, data: &NodeData) -> String {
+    match data {
+      NodeData::Node(id) => self
+        .forest
+        .main_tree()
+        .get_node_by_id(*id)
+        .map(|node| node.path())
+        .unwrap_or_else(|| data.to_string()),
+      _ => data.to_string(),
+    }
+  }
+
   fn open_node_data(&mut self, data: &NodeData) -> Result<(), AppError> {
     match data {
       NodeData::File(path) => self.open_node_file(path),
       NodeData::Link(url) => self.open_node_link(url),
+      NodeData::Node(id) => self.jump_to_node(*id),
     }
   }
 
@@ -286,6 +444,15 @@ impl App {
     })
   }
 
+  fn jump_to_node(&self, id: NodeId) -> Result<(), AppError> {
+    log::info!("jumping to node {id}");
+
+    match self.forest.main_tree().get_node_by_id(id) {
+      Some(node) => self.request(Request::JumpToNode { node }),
+      None => self.request(Request::err_msg("referenced node doesn’t exist anymore")),
+    }
+  }
+
   fn user_input(&self, title: impl Into<String>) -> Result<Option<String>, AppError> {
     let (sender, rx) = channel();
     self.request(Request::UserInput {
@@ -311,14 +478,43 @@ impl App {
           self.config.ui.extension.as_deref().unwrap_or(".md"),
           "",
         )?;
-        node.set_data(NodeData::File(path.clone()))?;
+        self.forest.main_tree().apply(Operation::Attach {
+          node,
+          data: NodeData::File(path.clone()),
+        })?;
         self.open_node_file(&path)?;
         self.dirty = true;
       } else if item.name == "url" {
         if let Some(url) = self.user_input("URL:")? {
-          node.set_data(NodeData::Link(url))?;
+          self.forest.main_tree().apply(Operation::Attach {
+            node,
+            data: NodeData::Link(url),
+          })?;
           self.dirty = true;
         }
+      } else if item.name == "node" {
+        if let Some(selection) = self.user_input("node (path or id:<ID>):")? {
+          let tree = self.forest.main_tree();
+          let target = match selection.strip_prefix("id:") {
+            Some(id) => Ok(id.parse().ok().and_then(|id| tree.get_node_by_id(id))),
+            None => tree.get_node_by_path(path_iter(&selection), false),
+          };
+
+          match target.and_then(|target| {
+            target
+              .map(|target| {
+                tree.apply(Operation::Attach {
+                  node,
+                  data: NodeData::node(target.id()),
+                })
+              })
+              .transpose()
+          }) {
+            Ok(Some(())) => self.dirty = true,
+            Ok(None) => self.request(Request::err_msg(format!("no such node: {selection}")))?,
+            Err(err) => self.request(Request::err_msg(err.to_string()))?,
+          }
+        }
       } else {
         log::warn!("unknown node data type: {item:?}");
       }
@@ -334,11 +530,67 @@ impl App {
         name = node.name()
       );
 
-      node.set_name(rename)?;
+      self
+        .forest
+        .main_tree()
+        .apply(Operation::Rename { node, name: rename })?;
       self.dirty = true;
       self.request(Request::RenamedNode { id })?;
     }
 
     Ok(())
   }
+
+  fn on_cycle_node_status(&mut self, id: usize) -> Result<(), AppError> {
+    let tree = self.forest.main_tree();
+    if let Some(node) = tree.get_node_by_line(id) {
+      let status = TaskStatus::cycle(node.status());
+      log::info!("node {id} status is now {status:?}");
+
+      tree.apply(Operation::SetStatus { node, status })?;
+      self.dirty = true;
+      self.request(Request::ChangedNodeStatus { id })?;
+    }
+
+    Ok(())
+  }
+
+  fn on_yank_node(&mut self, id: usize) -> Result<(), AppError> {
+    if let Some(node) = self.forest.main_tree().get_node_by_line(id) {
+      // copy the node right away, so that later changes to it are not pasted
+      self.yanked = Some(node.deep_copy());
+      self.request(Request::info_msg(format!("yanked {}", node.name())))?;
+    }
+
+    Ok(())
+  }
+
+  fn on_paste_node(&mut self, id: usize, mode: InsertMode) -> Result<(), AppError> {
+    let Some(ref yanked) = self.yanked else {
+      return self.request(Request::warn_msg("nothing to paste"));
+    };
+
+    let tree = self.forest.main_tree();
+    if let Some(anchor) = tree.get_node_by_line(id) {
+      let node = yanked.deep_copy();
+      let operation = match mode {
+        InsertMode::InsideTop => Ok(Operation::insert_top(anchor, node.clone())),
+        InsertMode::InsideBottom => Ok(Operation::insert_bottom(anchor, node.clone())),
+        InsertMode::Before => Operation::insert_before(&anchor, node.clone()),
+        InsertMode::After => Operation::insert_after(&anchor, node.clone()),
+      };
+
+      // pasting next to the root fails, which is reported rather than aborting
+      match operation.and_then(|operation| tree.apply(operation)) {
+        Ok(()) => {
+          self.dirty = true;
+          self.request(Request::JumpToNode { node })?;
+        }
+
+        Err(err) => self.request(Request::err_msg(err.to_string()))?,
+      }
+    }
+
+    Ok(())
+  }
 }
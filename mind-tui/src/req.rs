@@ -1,5 +1,7 @@
 use std::{path::PathBuf, str::FromStr, sync::mpsc::Sender, time::Duration};
 
+use mind_tree::node::{Node, SortKey};
+
 use tui::{
   style::{Color, Style},
   text::Span,
@@ -35,18 +37,46 @@ pub enum Request {
   /// A node was renamed.
   RenamedNode { id: usize },
 
+  /// The task status of a node changed.
+  ChangedNodeStatus { id: usize },
+
   /// Ask the TUI to create node data (prompt the user for the menu).
   PromptNodeData {
     // Sender to reply with.
     sender: Sender<Option<MenuItem>>,
   },
 
+  /// Ask the TUI which attachment of a node to open (prompt the user for the menu).
+  PromptNodeAttachment {
+    attachments: Vec<String>,
+
+    // Sender to reply with.
+    sender: Sender<Option<MenuItem>>,
+  },
+
+  /// Ask the TUI which node matching a query to jump to (prompt the user for the menu).
+  PromptQueryResult {
+    paths: Vec<String>,
+
+    // Sender to reply with.
+    sender: Sender<Option<MenuItem>>,
+  },
+
   /// Input the user.
   UserInput {
     title: String,
     sender: Sender<Option<String>>,
   },
 
+  /// Ask the TUI to select a node, e.g. when following a node reference.
+  JumpToNode { node: Node },
+
+  /// Ask the TUI which node is currently selected.
+  GetSelectedNode {
+    // Sender to reply with.
+    sender: Sender<Node>,
+  },
+
   /// Ask the TUI to open the editor with on the given path.
   OpenEditor { path: PathBuf },
 }
@@ -89,16 +119,59 @@ pub enum UserCmd {
 
   /// The user wants to save the current tree.
   Save,
+
+  /// The user wants to find nodes matching a query.
+  Query(String),
+
+  /// The user wants to undo the last change.
+  Undo,
+
+  /// The user wants to redo the last undone change.
+  Redo,
+
+  /// The user wants to sort the children of the selected node.
+  Sort {
+    key: SortKey,
+    reverse: bool,
+    recursive: bool,
+  },
 }
 
 impl FromStr for UserCmd {
   type Err = AppError;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if let Some(query) = s.strip_prefix("query ") {
+      return Ok(UserCmd::Query(query.trim().to_owned()));
+    }
+
+    let mut words = s.split_whitespace();
+    if words.next() == Some("sort") {
+      let mut key = SortKey::Name;
+      let mut reverse = false;
+      let mut recursive = false;
+
+      for word in words {
+        match word {
+          "-r" | "--recursive" => recursive = true,
+          "--reverse" => reverse = true,
+          _ => key = word.parse()?,
+        }
+      }
+
+      return Ok(UserCmd::Sort {
+        key,
+        reverse,
+        recursive,
+      });
+    }
+
     match s {
       "q" | "quit" => Ok(UserCmd::Quit { force: false }),
       "q!" | "quit!" => Ok(UserCmd::Quit { force: true }),
       "w" | "write" => Ok(UserCmd::Save),
+      "u" | "undo" => Ok(UserCmd::Undo),
+      "redo" => Ok(UserCmd::Redo),
       _ => Err(AppError::UnknownCommand(s.to_owned())),
     }
   }
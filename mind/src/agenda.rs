@@ -0,0 +1,160 @@
+//! Agenda of dated nodes.
+
+use crate::node::{Node, TaskStatus};
+use chrono::{Duration, NaiveDate};
+use std::{collections::HashSet, fmt};
+
+/// Kind of date a node appears in the agenda for.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DateKind {
+  Scheduled,
+  Due,
+}
+
+impl DateKind {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      DateKind::Scheduled => "scheduled",
+      DateKind::Due => "due",
+    }
+  }
+}
+
+impl fmt::Display for DateKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// Section of the agenda an item belongs to.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum AgendaSection {
+  Overdue,
+  Today,
+  Upcoming,
+}
+
+/// A dated node in the agenda.
+#[derive(Clone, Debug)]
+pub struct AgendaItem {
+  pub node: Node,
+  pub kind: DateKind,
+  pub date: NaiveDate,
+}
+
+impl AgendaItem {
+  /// Section of the agenda this item belongs to, relative to `today`.
+  pub fn section(&self, today: NaiveDate) -> AgendaSection {
+    if self.date < today {
+      AgendaSection::Overdue
+    } else if self.date == today {
+      AgendaSection::Today
+    } else {
+      AgendaSection::Upcoming
+    }
+  }
+}
+
+/// Collect the agenda items of a subtree, sorted by date.
+///
+/// Nodes appear once per date they have (scheduled and due); cloned nodes appear at their first occurrence only. Done
+/// and cancelled tasks are ignored, as well as items more than `days` days after `today`. Items in the past are always
+/// kept, as they are overdue. Horizons past the last representable date keep all the items.
+pub fn agenda(root: &Node, today: NaiveDate, days: u32) -> Vec<AgendaItem> {
+  let horizon = today
+    .checked_add_signed(Duration::days(days.into()))
+    .unwrap_or(NaiveDate::MAX);
+  let mut items = Vec::new();
+  let mut seen = HashSet::new();
+
+  for node in root.subtree() {
+    if !seen.insert(node.id()) {
+      continue;
+    }
+
+    if let Some(TaskStatus::Done | TaskStatus::Cancelled) = node.status() {
+      continue;
+    }
+
+    let dates = [
+      (DateKind::Scheduled, node.scheduled()),
+      (DateKind::Due, node.due()),
+    ];
+
+    for (kind, date) in dates {
+      if let Some(date) = date.filter(|date| *date <= horizon) {
+        items.push(AgendaItem {
+          node: node.clone(),
+          kind,
+          date,
+        });
+      }
+    }
+  }
+
+  items.sort_by_key(|item| (item.date, item.kind));
+  items
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{agenda, AgendaSection, DateKind};
+  use crate::node::{Node, TaskStatus};
+  use chrono::NaiveDate;
+
+  fn date(d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2023, 6, d).unwrap()
+  }
+
+  #[test]
+  fn sections() {
+    let root = Node::new("root", "");
+    let late = Node::new("late", "");
+    let now = Node::new("now", "");
+    let soon = Node::new("soon", "");
+    let later = Node::new("later", "");
+    let done = Node::new("done", "");
+    root.insert_bottom(late.clone());
+    root.insert_bottom(now.clone());
+    root.insert_bottom(soon.clone());
+    root.insert_bottom(later.clone());
+    root.insert_bottom(done.clone());
+
+    late.set_due(Some(date(10)));
+    now.set_scheduled(Some(date(14)));
+    now.set_due(Some(date(16)));
+    later.set_due(Some(date(30)));
+    done.set_due(Some(date(1)));
+    done.set_status(Some(TaskStatus::Done));
+
+    let items = agenda(&root, date(14), 7);
+    let items: Vec<_> = items
+      .iter()
+      .map(|item| (item.node.name(), item.kind, item.section(date(14))))
+      .collect();
+
+    assert_eq!(
+      items,
+      vec![
+        ("late".to_owned(), DateKind::Due, AgendaSection::Overdue),
+        ("now".to_owned(), DateKind::Scheduled, AgendaSection::Today),
+        ("now".to_owned(), DateKind::Due, AgendaSection::Upcoming),
+      ]
+    );
+  }
+
+  #[test]
+  fn unbounded_horizon() {
+    let root = Node::new("root", "");
+    let late = Node::new("late", "");
+    let last = Node::new("last", "");
+    root.insert_bottom(late.clone());
+    root.insert_bottom(last.clone());
+    late.set_due(Some(date(10)));
+    last.set_due(Some(NaiveDate::MAX));
+
+    let items = agenda(&root, date(14), u32::MAX);
+    let names: Vec<_> = items.iter().map(|item| item.node.name()).collect();
+    assert_eq!(names, ["late", "last"]);
+  }
+}
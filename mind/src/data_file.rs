@@ -1,7 +1,12 @@
 //! Filesystem features, such as creating data files for data nodes.
 
+use crate::node::{NodeData, Tree};
 use chrono::{Datelike, Timelike};
-use std::{fs, io, path::PathBuf};
+use std::{
+  collections::HashSet,
+  fs, io,
+  path::{Path, PathBuf},
+};
 use thiserror::Error;
 
 /// Errors that might happen when dealing with data file stores.
@@ -11,6 +16,9 @@ pub enum DataFileStoreError {
   FileSystemError(#[from] io::Error),
 }
 
+/// Name of the directory, inside the store, where orphaned data files are moved to.
+const TRASH_DIR: &str = "trash";
+
 /// Store for creating data files.
 #[derive(Debug)]
 pub struct DataFileStore {
@@ -29,12 +37,112 @@ impl DataFileStore {
     ext: impl AsRef<str>,
     contents: impl AsRef<str>,
   ) -> Result<PathBuf, DataFileStoreError> {
+    let path = self.new_path(name.as_ref(), ext.as_ref());
+
+    fs::create_dir_all(&self.root)?;
+    fs::write(&path, contents.as_ref())?;
+    Ok(path)
+  }
+
+  /// Copy an existing file into a new data file with the (sanitized) input name, keeping its extension.
+  pub fn copy_data_file(
+    &self,
+    name: impl AsRef<str>,
+    source: impl AsRef<Path>,
+  ) -> Result<PathBuf, DataFileStoreError> {
+    let source = source.as_ref();
+    let ext = source
+      .extension()
+      .map(|ext| format!(".{}", ext.to_string_lossy()))
+      .unwrap_or_default();
+    let path = self.new_path(name.as_ref(), &ext);
+
+    fs::create_dir_all(&self.root)?;
+    fs::copy(source, &path)?;
+    Ok(path)
+  }
+
+  /// Path of the directory orphaned data files are moved to.
+  pub fn trash_dir(&self) -> PathBuf {
+    self.root.join(TRASH_DIR)
+  }
+
+  /// Data files of the store, sorted by path.
+  ///
+  /// Trashed data files are not included.
+  pub fn data_files(&self) -> Result<Vec<PathBuf>, DataFileStoreError> {
+    let entries = match fs::read_dir(&self.root) {
+      Ok(entries) => entries,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+      Err(err) => return Err(err.into()),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+      let entry = entry?;
+      if entry.file_type()?.is_file() {
+        paths.push(entry.path());
+      }
+    }
+
+    paths.sort();
+    Ok(paths)
+  }
+
+  /// Data files of the store that no node of the trees is attached to, sorted by path.
+  ///
+  /// Data files might be referenced by trees that are not passed, so the result should only be trusted if all the trees
+  /// using the store are.
+  pub fn orphaned_data_files<'a>(
+    &self,
+    trees: impl IntoIterator<Item = &'a Tree>,
+  ) -> Result<Vec<PathBuf>, DataFileStoreError> {
+    let referenced: HashSet<_> = trees
+      .into_iter()
+      .flat_map(|tree| tree.root().subtree())
+      .flat_map(|node| node.attachments())
+      .filter_map(|data| match data {
+        NodeData::File(path) => Some(canonicalize(&path)),
+        _ => None,
+      })
+      .collect();
+
+    Ok(
+      self
+        .data_files()?
+        .into_iter()
+        .filter(|path| !referenced.contains(&canonicalize(path)))
+        .collect(),
+    )
+  }
+
+  /// Move a data file to the trash directory, returning its new path.
+  pub fn trash(&self, path: impl AsRef<Path>) -> Result<PathBuf, DataFileStoreError> {
+    let path = path.as_ref();
+    let trash_dir = self.trash_dir();
+    let name = path.file_name().ok_or_else(|| {
+      io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{} is not a file", path.display()),
+      )
+    })?;
+
+    fs::create_dir_all(&trash_dir)?;
+    let trashed = unique_path(trash_dir.join(name));
+    fs::rename(path, &trashed)?;
+    Ok(trashed)
+  }
+
+  /// Path of a new data file, prefixed with the current date and time.
+  ///
+  /// A number is appended to the name if a data file with the same name was already created within the same second.
+  fn new_path(&self, name: &str, ext: &str) -> PathBuf {
     // sanitize the name first
-    let sanitized = Self::sanitize_name(name.as_ref());
+    let sanitized = Self::sanitize_name(name);
 
     let now = chrono::Utc::now();
-    let name = format!(
-      "{year}{month}{day}{hour}{minute}{second}-{name}{ext}",
+    let prefix = format!(
+      "{year}{month}{day}{hour}{minute}{second}-{name}",
       year = now.year(),
       month = now.month(),
       day = now.day(),
@@ -42,13 +150,9 @@ impl DataFileStore {
       minute = now.minute(),
       second = now.second(),
       name = sanitized,
-      ext = ext.as_ref()
     );
-    let path = self.root.join(name);
 
-    fs::create_dir_all(&self.root)?;
-    fs::write(&path, contents.as_ref())?;
-    Ok(path)
+    unique_path(self.root.join(format!("{prefix}{ext}")))
   }
 
   fn sanitize_name(name: &str) -> String {
@@ -67,3 +171,74 @@ impl DataFileStore {
       .collect()
   }
 }
+
+/// Append a number to the name of a file if it already exists, until it doesn’t.
+fn unique_path(path: PathBuf) -> PathBuf {
+  let stem = path
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().into_owned())
+    .unwrap_or_default();
+  let ext = path
+    .extension()
+    .map(|ext| format!(".{}", ext.to_string_lossy()))
+    .unwrap_or_default();
+
+  let mut unique = path.clone();
+  let mut n = 1;
+  while unique.exists() {
+    n += 1;
+    unique = path.with_file_name(format!("{stem}-{n}{ext}"));
+  }
+
+  unique
+}
+
+/// Canonical form of a path, or the path itself if it doesn’t exist.
+fn canonicalize(path: &Path) -> PathBuf {
+  path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::DataFileStore;
+  use crate::node::{Node, NodeData, NodeId, Tree};
+  use std::{env, fs};
+
+  #[test]
+  fn orphaned_data_files() {
+    let dir = env::temp_dir().join(format!("mind-gc-{}", NodeId::new()));
+    let store = DataFileStore::new(&dir);
+    assert!(store.data_files().unwrap().is_empty());
+
+    let kept = store.create_data_file("kept", ".md", "").unwrap();
+    let orphan = store.create_data_file("orphan", ".md", "").unwrap();
+    let other = store.create_data_file("other", ".md", "").unwrap();
+
+    let a = Tree::new("a", "");
+    let b = Tree::new("b", "");
+    let node = Node::new("node", "");
+    a.root().insert_bottom(node.clone());
+    node.add_attachment(NodeData::file(&kept)).unwrap();
+    b.root().add_attachment(NodeData::file(&other)).unwrap();
+
+    assert_eq!(
+      store.orphaned_data_files([&a, &b]).unwrap(),
+      vec![orphan.clone()]
+    );
+    assert_eq!(
+      store.orphaned_data_files([&a]).unwrap(),
+      [orphan.clone(), other]
+    );
+
+    // trashed files are not data files anymore, and never overwrite each other
+    let trashed = store.trash(&orphan).unwrap();
+    assert!(!orphan.exists());
+    assert_eq!(trashed.parent(), Some(store.trash_dir().as_path()));
+    assert!(store.orphaned_data_files([&a, &b]).unwrap().is_empty());
+
+    fs::write(&orphan, "").unwrap();
+    assert_ne!(store.trash(&orphan).unwrap(), trashed);
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
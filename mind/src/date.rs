@@ -0,0 +1,128 @@
+//! Date parsing, supporting both absolute and relative dates.
+
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use thiserror::Error;
+
+/// Errors that might happen when parsing dates.
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum DateError {
+  #[error(
+    "invalid date '{0}'; expected YYYY-MM-DD, today, tomorrow, yesterday, a weekday or +N(d|w|m)"
+  )]
+  InvalidDate(String),
+}
+
+/// Parse a date, relative to `today`.
+///
+/// The following forms are supported:
+///
+/// - Absolute dates, as `YYYY-MM-DD`.
+/// - `today`, `tomorrow` and `yesterday`.
+/// - Offsets, as `+N` or `-N` followed by a unit: `d` (days), `w` (weeks) or `m` (months); e.g. `+3d`.
+/// - Weekdays, either full (`friday`) or abbreviated (`fri`); they refer to the next such day, never today.
+pub fn parse_date(input: &str, today: NaiveDate) -> Result<NaiveDate, DateError> {
+  let input = input.trim();
+  let invalid = || DateError::InvalidDate(input.to_owned());
+
+  match input.to_lowercase().as_str() {
+    "today" => return Ok(today),
+    "tomorrow" => return Ok(today + Duration::days(1)),
+    "yesterday" => return Ok(today - Duration::days(1)),
+    _ => (),
+  }
+
+  if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+    return Ok(date);
+  }
+
+  if let Some(offset) = input.strip_prefix('+') {
+    return parse_offset(offset, today, false).ok_or_else(invalid);
+  }
+
+  if let Some(offset) = input.strip_prefix('-') {
+    return parse_offset(offset, today, true).ok_or_else(invalid);
+  }
+
+  let weekday = input.parse::<Weekday>().map_err(|_| invalid())?;
+  let days =
+    (7 + weekday.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64) % 7;
+  let days = if days == 0 { 7 } else { days };
+
+  Ok(today + Duration::days(days))
+}
+
+/// Parse `N(d|w|m)` and apply it to `today`.
+fn parse_offset(offset: &str, today: NaiveDate, backwards: bool) -> Option<NaiveDate> {
+  let unit = offset.chars().last()?;
+  let n: u32 = offset[..offset.len() - unit.len_utf8()].parse().ok()?;
+
+  match unit {
+    'd' | 'w' => {
+      let days = if unit == 'w' { n as i64 * 7 } else { n as i64 };
+
+      if backwards {
+        today.checked_sub_signed(Duration::days(days))
+      } else {
+        today.checked_add_signed(Duration::days(days))
+      }
+    }
+
+    'm' if backwards => today.checked_sub_months(Months::new(n)),
+    'm' => today.checked_add_months(Months::new(n)),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{parse_date, DateError};
+  use chrono::NaiveDate;
+
+  fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+  }
+
+  // a Wednesday
+  fn today() -> NaiveDate {
+    date(2023, 6, 14)
+  }
+
+  #[test]
+  fn absolute() {
+    assert_eq!(parse_date("2023-07-01", today()), Ok(date(2023, 7, 1)));
+  }
+
+  #[test]
+  fn named() {
+    assert_eq!(parse_date("today", today()), Ok(today()));
+    assert_eq!(parse_date("Tomorrow", today()), Ok(date(2023, 6, 15)));
+    assert_eq!(parse_date("yesterday", today()), Ok(date(2023, 6, 13)));
+  }
+
+  #[test]
+  fn offsets() {
+    assert_eq!(parse_date("+3d", today()), Ok(date(2023, 6, 17)));
+    assert_eq!(parse_date("+2w", today()), Ok(date(2023, 6, 28)));
+    assert_eq!(parse_date("+1m", today()), Ok(date(2023, 7, 14)));
+    assert_eq!(parse_date("-1d", today()), Ok(date(2023, 6, 13)));
+  }
+
+  #[test]
+  fn weekdays() {
+    assert_eq!(parse_date("fri", today()), Ok(date(2023, 6, 16)));
+    assert_eq!(parse_date("monday", today()), Ok(date(2023, 6, 19)));
+
+    // the same weekday as today refers to next week
+    assert_eq!(parse_date("wed", today()), Ok(date(2023, 6, 21)));
+  }
+
+  #[test]
+  fn invalid() {
+    for input in ["", "+d", "+3y", "soon", "2023-13-01"] {
+      assert_eq!(
+        parse_date(input, today()),
+        Err(DateError::InvalidDate(input.to_owned()))
+      );
+    }
+  }
+}
@@ -0,0 +1,196 @@
+//! Structural differences between trees.
+
+use crate::node::{Node, NodeData, NodeId, Tree};
+use std::collections::{HashMap, HashSet};
+
+/// A change of a node between two trees.
+///
+/// Nodes are matched by [`NodeId`]; `old` nodes belong to the old tree and `new` nodes to the new one. A node can have
+/// several changes, e.g. if it was both renamed and moved.
+#[derive(Clone, Debug)]
+pub enum Change {
+  /// The node exists only in the new tree.
+  Added { new: Node },
+
+  /// The node exists only in the old tree.
+  Removed { old: Node },
+
+  /// The node has a different name.
+  Renamed { old: Node, new: Node },
+
+  /// The node has a different parent.
+  Moved { old: Node, new: Node },
+
+  /// The node has a different icon.
+  IconChanged { old: Node, new: Node },
+
+  /// The node has different attachments.
+  DataChanged { old: Node, new: Node },
+}
+
+impl Change {
+  /// Node the change is about, in the new tree if it exists there.
+  pub fn node(&self) -> &Node {
+    match self {
+      Change::Added { new }
+      | Change::Renamed { new, .. }
+      | Change::Moved { new, .. }
+      | Change::IconChanged { new, .. }
+      | Change::DataChanged { new, .. } => new,
+      Change::Removed { old } => old,
+    }
+  }
+}
+
+/// Compute the changes between two trees.
+///
+/// Removed nodes come first, in the order of the old tree, followed by the other changes, in the order of the new tree.
+/// Moving a node among its siblings is not a change; cloned nodes are compared at their first occurrence only.
+pub fn diff(old: &Tree, new: &Tree) -> Vec<Change> {
+  let old_nodes = nodes_by_id(&old.root());
+  let new_nodes = nodes_by_id(&new.root());
+  let new_ids: HashSet<_> = new_nodes.iter().map(|(id, _)| *id).collect();
+  let mut changes = Vec::new();
+
+  for (id, old) in &old_nodes {
+    if !new_ids.contains(id) {
+      changes.push(Change::Removed { old: old.clone() });
+    }
+  }
+
+  let old_nodes: HashMap<_, _> = old_nodes.into_iter().collect();
+  for (id, new) in new_nodes {
+    let old = match old_nodes.get(&id) {
+      Some(old) => old.clone(),
+      None => {
+        changes.push(Change::Added { new });
+        continue;
+      }
+    };
+
+    let old_parent = old.parent().ok().map(|parent| parent.id());
+    let new_parent = new.parent().ok().map(|parent| parent.id());
+
+    if old.name() != new.name() {
+      changes.push(Change::Renamed {
+        old: old.clone(),
+        new: new.clone(),
+      });
+    }
+
+    if old_parent != new_parent {
+      changes.push(Change::Moved {
+        old: old.clone(),
+        new: new.clone(),
+      });
+    }
+
+    if old.icon() != new.icon() {
+      changes.push(Change::IconChanged {
+        old: old.clone(),
+        new: new.clone(),
+      });
+    }
+
+    if old.attachments() != new.attachments() {
+      changes.push(Change::DataChanged { old, new });
+    }
+  }
+
+  changes
+}
+
+/// Attachments of `new` that `old` doesn’t have, and attachments of `old` that `new` doesn’t have.
+pub fn data_changes(old: &Node, new: &Node) -> (Vec<NodeData>, Vec<NodeData>) {
+  let old = old.attachments();
+  let new = new.attachments();
+  let added = new
+    .iter()
+    .filter(|data| !old.contains(data))
+    .cloned()
+    .collect();
+  let removed = old.into_iter().filter(|data| !new.contains(data)).collect();
+  (added, removed)
+}
+
+/// Nodes of a subtree with their IDs, in order and without duplicate clones.
+fn nodes_by_id(root: &Node) -> Vec<(NodeId, Node)> {
+  let mut seen = HashSet::new();
+
+  root
+    .subtree()
+    .into_iter()
+    .map(|node| (node.id(), node))
+    .filter(|(id, _)| seen.insert(*id))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{data_changes, diff, Change};
+  use crate::node::{Node, NodeData, Tree};
+
+  fn describe(changes: &[Change]) -> Vec<String> {
+    changes
+      .iter()
+      .map(|change| {
+        let kind = match change {
+          Change::Added { .. } => "added",
+          Change::Removed { .. } => "removed",
+          Change::Renamed { .. } => "renamed",
+          Change::Moved { .. } => "moved",
+          Change::IconChanged { .. } => "icon",
+          Change::DataChanged { .. } => "data",
+        };
+        format!("{kind} {}", change.node().path())
+      })
+      .collect()
+  }
+
+  #[test]
+  fn changes() {
+    let old = Tree::new("root", "");
+    let root = old.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    let c = Node::new("c", "");
+    let d = Node::new("d", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(b.clone());
+    a.insert_bottom(c.clone());
+    a.insert_bottom(d.clone());
+
+    // the new tree is a copy of the old one, with the same IDs
+    let new: Tree = serde_json::from_str(&serde_json::to_string(&old).unwrap()).unwrap();
+    assert!(diff(&old, &new).is_empty());
+
+    let new_b = new.get_node_by_id(b.id()).unwrap();
+    let new_c = new.get_node_by_id(c.id()).unwrap();
+    let new_d = new.get_node_by_id(d.id()).unwrap();
+    new_c.parent().unwrap().delete(new_c).unwrap();
+    new_d.set_name("e").unwrap();
+    new_d.set_icon("x");
+    new_b.move_bottom(new_d.clone()).unwrap();
+    new_b.insert_bottom(Node::new("f", ""));
+    new_b
+      .add_attachment(NodeData::link("https://foo.bar"))
+      .unwrap();
+
+    let changes = diff(&old, &new);
+    assert_eq!(
+      describe(&changes),
+      [
+        "removed /a/c",
+        "data /b",
+        "renamed /b/e",
+        "moved /b/e",
+        "icon /b/e",
+        "added /b/f",
+      ]
+    );
+
+    let (added, removed) = data_changes(&b, &new_b);
+    assert_eq!(added, [NodeData::link("https://foo.bar")]);
+    assert!(removed.is_empty());
+  }
+}
@@ -0,0 +1,364 @@
+//! Integrity checks of trees and forests, and repair of what can be repaired.
+
+use crate::{
+  encoding::Version,
+  forest::Forest,
+  node::{Node, NodeData, NodeError, NodeId, Tree},
+};
+use serde::Deserialize;
+use std::{
+  collections::{HashMap, HashSet},
+  fmt, iter,
+  path::PathBuf,
+};
+
+/// Name given to nodes with an empty name when fixing them.
+const UNNAMED: &str = "unnamed";
+
+/// An integrity issue.
+#[derive(Clone, Debug)]
+pub enum Issue {
+  /// A child of `parent` doesn’t point back to it as its parent.
+  DanglingParent { parent: Node, child: Node },
+
+  /// The previous or next sibling links of a child of `parent` don’t match its position among the children.
+  BrokenSiblingLinks { parent: Node, child: Node },
+
+  /// A child of `parent` has the same name as one of its previous siblings.
+  ///
+  /// Siblings sharing a name are legitimate and can be told apart with `name[N]` in paths, so this issue is only
+  /// informational.
+  DuplicateName { parent: Node, child: Node },
+
+  /// The node has an empty name.
+  EmptyName { node: Node },
+
+  /// A data file attached to the node doesn’t exist.
+  MissingDataFile { node: Node, path: PathBuf },
+
+  /// The directory of a CWD tree doesn’t exist anymore.
+  MissingProjectDir { path: PathBuf },
+
+  /// The tree was encoded with an unknown version, so it cannot be loaded.
+  UnknownVersion { version: Version },
+}
+
+impl Issue {
+  /// Check whether the issue is only informational, i.e. worth reporting but not an integrity error.
+  pub fn is_informational(&self) -> bool {
+    matches!(self, Issue::DuplicateName { .. })
+  }
+
+  /// Check whether the issue can be fixed with [`Issue::fix`].
+  ///
+  /// Missing project directories and unknown versions are never fixed, as that would mean discarding whole trees.
+  /// Informational issues are never fixed either.
+  pub fn is_fixable(&self) -> bool {
+    !self.is_informational()
+      && !matches!(
+        self,
+        Issue::MissingProjectDir { .. } | Issue::UnknownVersion { .. }
+      )
+  }
+
+  /// Fix the issue, returning whether it was fixable.
+  ///
+  /// - Links are repaired from the children of the parent; children claimed by another parent are removed.
+  /// - Empty names are replaced by a name that none of the siblings have, e.g. `unnamed (2)`.
+  /// - Missing data files are detached.
+  pub fn fix(&self) -> Result<bool, NodeError> {
+    match self {
+      Issue::DanglingParent { parent, .. } | Issue::BrokenSiblingLinks { parent, .. } => {
+        parent.repair_children();
+      }
+
+      Issue::EmptyName { node } => {
+        let names: HashSet<_> = node
+          .parent()
+          .map(|parent| parent.children().into_iter().map(Node::name).collect())
+          .unwrap_or_default();
+        let name = iter::once(UNNAMED.to_owned())
+          .chain((2..).map(|n| format!("{UNNAMED} ({n})")))
+          .find(|name| !names.contains(name))
+          .unwrap_or_else(|| UNNAMED.to_owned());
+        node.set_name(name)?;
+      }
+
+      Issue::MissingDataFile { node, path } => {
+        let attachments = node
+          .attachments()
+          .into_iter()
+          .filter(|data| !matches!(data, NodeData::File(file) if file == path))
+          .collect();
+        node.set_attachments(attachments);
+      }
+
+      Issue::DuplicateName { .. }
+      | Issue::MissingProjectDir { .. }
+      | Issue::UnknownVersion { .. } => return Ok(false),
+    }
+
+    Ok(true)
+  }
+}
+
+impl fmt::Display for Issue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Issue::DanglingParent { parent, child } => write!(
+        f,
+        "{}: child '{}' doesn’t point back to it as its parent",
+        parent.path(),
+        child.name()
+      ),
+
+      Issue::BrokenSiblingLinks { parent, child } => write!(
+        f,
+        "{}: child '{}' has broken sibling links",
+        parent.path(),
+        child.name()
+      ),
+
+      Issue::DuplicateName { parent, child } => write!(
+        f,
+        "{}: several children are named '{}'",
+        parent.path(),
+        child.name()
+      ),
+
+      Issue::EmptyName { node } => write!(f, "{}: empty name", node.path()),
+
+      Issue::MissingDataFile { node, path } => {
+        write!(f, "{}: missing data file {}", node.path(), path.display())
+      }
+
+      Issue::MissingProjectDir { path } => {
+        write!(f, "directory {} doesn’t exist anymore", path.display())
+      }
+
+      Issue::UnknownVersion { version } => write!(f, "unknown tree version {version}"),
+    }
+  }
+}
+
+/// Check the integrity of a tree.
+///
+/// Issues are reported in the order of the tree. Issues about the content of cloned nodes are reported only once.
+pub fn check_tree(tree: &Tree) -> Vec<Issue> {
+  let mut issues = Vec::new();
+  check_node(&tree.root(), &mut HashSet::new(), &mut issues);
+  issues
+}
+
+fn check_node(node: &Node, checked: &mut HashSet<NodeId>, issues: &mut Vec<Issue>) {
+  if checked.insert(node.id()) {
+    if node.name().is_empty() {
+      issues.push(Issue::EmptyName { node: node.clone() });
+    }
+
+    for data in node.attachments() {
+      match data {
+        NodeData::File(path) if !path.exists() => issues.push(Issue::MissingDataFile {
+          node: node.clone(),
+          path,
+        }),
+        _ => (),
+      }
+    }
+  }
+
+  let children: Vec<_> = node.children().into_iter().cloned().collect();
+  let mut names = HashMap::new();
+
+  for (i, child) in children.iter().enumerate() {
+    if !matches!(child.parent(), Ok(parent) if &parent == node) {
+      issues.push(Issue::DanglingParent {
+        parent: node.clone(),
+        child: child.clone(),
+      });
+    }
+
+    let prev = i.checked_sub(1).map(|i| &children[i]);
+    if child.prev().as_ref() != prev || child.next().as_ref() != children.get(i + 1) {
+      issues.push(Issue::BrokenSiblingLinks {
+        parent: node.clone(),
+        child: child.clone(),
+      });
+    }
+
+    // empty names are reported on their own
+    let count = names.entry(child.name()).or_insert(0);
+    *count += 1;
+    if *count > 1 && !child.name().is_empty() {
+      issues.push(Issue::DuplicateName {
+        parent: node.clone(),
+        child: child.clone(),
+      });
+    }
+  }
+
+  for child in &children {
+    check_node(child, checked, issues);
+  }
+}
+
+/// Check the integrity of all the trees of a forest.
+///
+/// Issues are paired with the directory of the CWD tree they are about, if any; otherwise, they are about the main
+/// tree.
+pub fn check_forest(forest: &Forest) -> Vec<(Option<PathBuf>, Issue)> {
+  let mut issues: Vec<_> = check_tree(forest.main_tree())
+    .into_iter()
+    .map(|issue| (None, issue))
+    .collect();
+
+  let mut projects: Vec<_> = forest.cwd_trees().collect();
+  projects.sort_by_key(|(path, _)| *path);
+
+  for (path, tree) in projects {
+    if !path.is_dir() {
+      issues.push((
+        Some(path.to_owned()),
+        Issue::MissingProjectDir {
+          path: path.to_owned(),
+        },
+      ));
+    }
+
+    issues.extend(
+      check_tree(tree)
+        .into_iter()
+        .map(|issue| (Some(path.to_owned()), issue)),
+    );
+  }
+
+  issues
+}
+
+/// Check the version of an encoded tree.
+///
+/// Trees with an unknown version cannot be loaded, so this check works on their JSON representation.
+pub fn check_encoded_tree(tree: &serde_json::Value) -> Option<Issue> {
+  let version = match tree.get("version") {
+    Some(version) => Version::deserialize(version).ok()?,
+
+    // trees without version predate versioning
+    None => Version::V1,
+  };
+
+  (!version.is_known()).then_some(Issue::UnknownVersion { version })
+}
+
+/// Check the versions of the trees of an encoded forest.
+///
+/// See [`check_encoded_tree`] and [`check_forest`].
+pub fn check_encoded_forest(forest: &serde_json::Value) -> Vec<(Option<PathBuf>, Issue)> {
+  let mut issues: Vec<_> = forest
+    .get("tree")
+    .and_then(check_encoded_tree)
+    .map(|issue| (None, issue))
+    .into_iter()
+    .collect();
+
+  if let Some(projects) = forest
+    .get("projects")
+    .and_then(|projects| projects.as_object())
+  {
+    for (path, tree) in projects {
+      if let Some(issue) = check_encoded_tree(tree) {
+        issues.push((Some(PathBuf::from(path)), issue));
+      }
+    }
+  }
+
+  issues
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{check_encoded_forest, check_tree, Issue};
+  use crate::node::{Node, NodeData, NodeFilter, Tree};
+  use serde_json::json;
+
+  fn describe(issues: &[Issue]) -> Vec<String> {
+    issues.iter().map(Issue::to_string).collect()
+  }
+
+  #[test]
+  fn check_and_fix() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    let x = Node::new("x", "");
+    let y = Node::new("y", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(b.clone());
+    a.insert_bottom(x.clone());
+    a.insert_bottom(y.clone());
+    b.insert_bottom(Node::new("y", ""));
+    b.insert_bottom(Node::new("y", ""));
+    b.insert_bottom(Node::new("", ""));
+    b.insert_bottom(Node::new("", ""));
+    y.add_attachment(NodeData::file("/does/not/exist.md"))
+      .unwrap();
+    y.add_attachment(NodeData::link("https://foo.bar")).unwrap();
+
+    // inserting a node without removing it first leaves it in both parents
+    b.insert_top(x.clone());
+
+    let issues = check_tree(&tree);
+    assert_eq!(
+      describe(&issues),
+      [
+        "/a: child 'x' doesn’t point back to it as its parent",
+        "/a: child 'x' has broken sibling links",
+        "/a/y: missing data file /does/not/exist.md",
+        "/b: several children are named 'y'",
+        "/b/[1]: empty name",
+        "/b/[2]: empty name",
+      ]
+    );
+
+    // siblings sharing a name are legitimate, so they are reported but left untouched
+    for issue in &issues {
+      assert_eq!(issue.fix().unwrap(), !issue.is_informational());
+      assert_eq!(issue.is_fixable(), !issue.is_informational());
+    }
+
+    let issues = check_tree(&tree);
+    assert_eq!(describe(&issues), ["/b: several children are named 'y'"]);
+    assert!(issues.iter().all(Issue::is_informational));
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      [
+        "/",
+        "/a",
+        "/a/y",
+        "/b",
+        "/b/x",
+        "/b/y[1]",
+        "/b/y[2]",
+        "/b/unnamed",
+        "/b/unnamed (2)"
+      ]
+    );
+    assert_eq!(y.attachments(), [NodeData::link("https://foo.bar")]);
+  }
+
+  #[test]
+  fn encoded_versions() {
+    let forest = json!({
+      "tree": { "version": 2, "type": "root", "text": "main" },
+      "projects": {
+        "/old": { "type": "root", "text": "old" },
+        "/new": { "version": 42, "type": "root", "text": "new" },
+      },
+    });
+
+    let issues = check_encoded_forest(&forest);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].0.as_deref(), Some("/new".as_ref()));
+    assert_eq!(issues[0].1.to_string(), "unknown tree version 42");
+  }
+}
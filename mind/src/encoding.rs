@@ -1,15 +1,31 @@
 //! Encoding representation of trees and nodes
+//!
+//! The types in this module always represent the current version of the encoding (see [`Version::current`]). Older
+//! versions are still supported when deserializing; they are migrated to the current version on the fly.
 
+pub mod v1;
+
+use crate::node::{NodeData, NodeId, TaskStatus};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{de::Error as _, Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum TreeType {
   /// A root.
-  Root = 0,
+  Root,
 
   /// A local root.
-  Local = 1,
+  Local,
+}
+
+impl TreeType {
+  fn as_str(&self) -> &'static str {
+    match self {
+      TreeType::Root => "root",
+      TreeType::Local => "local",
+    }
+  }
 }
 
 impl Serialize for TreeType {
@@ -17,7 +33,7 @@ impl Serialize for TreeType {
   where
     S: serde::Serializer,
   {
-    (*self as u8).serialize(serializer)
+    self.as_str().serialize(serializer)
   }
 }
 
@@ -26,18 +42,17 @@ impl<'de> Deserialize<'de> for TreeType {
   where
     D: serde::Deserializer<'de>,
   {
-    match u8::deserialize(deserializer)? {
-      0 => Ok(TreeType::Root),
-      1 => Ok(TreeType::Local),
-      _ => Err(D::Error::custom("nope")),
+    match String::deserialize(deserializer)?.as_str() {
+      "root" => Ok(TreeType::Root),
+      "local" => Ok(TreeType::Local),
+      ty => Err(D::Error::custom(format!("unknown tree type: {ty}"))),
     }
   }
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 pub struct Tree {
   /// Protocol version the tree is compatible with.
-  #[serde(default)]
   pub version: Version,
 
   /// Type of node.
@@ -49,8 +64,66 @@ pub struct Tree {
   pub node: Node,
 }
 
+impl<'de> Deserialize<'de> for Tree {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    // we need to know the version before knowing how to interpret the rest of the tree
+    let value = serde_json::Value::deserialize(deserializer)?;
+    let version = match value.get("version") {
+      Some(version) => Version::deserialize(version).map_err(D::Error::custom)?,
+
+      // trees without version predate versioning, so they are Version 1 trees
+      None => Version::V1,
+    };
+
+    match version {
+      Version::V1 => {
+        log::debug!(
+          "migrating tree from version {version} to {}",
+          Version::current()
+        );
+
+        v1::Tree::deserialize(value)
+          .map(Tree::from)
+          .map_err(D::Error::custom)
+      }
+
+      Version::V2 => {
+        let tree = CurrentTree::deserialize(value).map_err(D::Error::custom)?;
+        Ok(Tree {
+          version: tree.version,
+          ty: tree.ty,
+          node: tree.node,
+        })
+      }
+
+      _ => Err(D::Error::custom(format!("unknown tree version: {version}"))),
+    }
+  }
+}
+
+/// Deserialization-only representation of a tree in the current version.
+#[derive(Deserialize)]
+struct CurrentTree {
+  version: Version,
+
+  #[serde(rename = "type")]
+  ty: TreeType,
+
+  #[serde(flatten)]
+  node: Node,
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Node {
+  /// Stable identifier of the node.
+  ///
+  /// Nodes persisted before identifiers existed get a new one when loaded.
+  #[serde(default)]
+  pub(crate) id: NodeId,
+
   /// Icon associated with this node.
   #[serde(default)]
   pub(crate) icon: String,
@@ -59,16 +132,51 @@ pub struct Node {
   #[serde(default)]
   pub(crate) is_expanded: bool,
 
+  /// Whether the node is a clone of another node with the same ID.
+  ///
+  /// Clones only store their position and children; everything else is stored by the node they are a clone of.
+  #[serde(default, skip_serializing_if = "is_false")]
+  pub(crate) is_clone: bool,
+
   /// Text associated with the node.
-  pub(crate) contents: Vec<Text>,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub(crate) text: String,
 
-  /// Data file associated with, if any.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub(crate) data: Option<PathBuf>,
+  /// Date and time at which the node was created, if known.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) created_at: Option<DateTime<Utc>>,
 
-  /// Link associated with, if any.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub(crate) url: Option<String>,
+  /// Date and time at which the node was last modified, if known.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) modified_at: Option<DateTime<Utc>>,
+
+  /// Tags of the node.
+  #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+  pub(crate) tags: BTreeSet<String>,
+
+  /// Free-form key/value properties of the node.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub(crate) properties: BTreeMap<String, String>,
+
+  /// Task status of the node, if it is a task.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) status: Option<TaskStatus>,
+
+  /// Body text of the node, if any.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) body: Option<String>,
+
+  /// Date at which the node is due, if any.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) due: Option<NaiveDate>,
+
+  /// Date at which the node is scheduled, if any.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) scheduled: Option<NaiveDate>,
+
+  /// Files and links attached to the node.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub(crate) attachments: Vec<NodeData>,
 
   /// Children nodes, if any.
   #[serde(default)]
@@ -76,7 +184,32 @@ pub struct Node {
   pub(crate) children: Vec<Node>,
 }
 
+fn is_false(b: &bool) -> bool {
+  !b
+}
+
 impl Node {
+  /// A node with the given ID and text, with everything else empty.
+  pub(crate) fn new(id: NodeId, text: String) -> Self {
+    Self {
+      id,
+      icon: String::new(),
+      is_expanded: false,
+      is_clone: false,
+      text,
+      created_at: None,
+      modified_at: None,
+      tags: BTreeSet::new(),
+      properties: BTreeMap::new(),
+      status: None,
+      body: None,
+      due: None,
+      scheduled: None,
+      attachments: Vec::new(),
+      children: Vec::new(),
+    }
+  }
+
   #[cfg(test)]
   pub(crate) fn new_by_expand_state(
     name: impl Into<String>,
@@ -84,12 +217,9 @@ impl Node {
     children: Vec<Node>,
   ) -> Self {
     Self {
-      icon: String::new(),
       is_expanded,
-      contents: vec![Text { text: name.into() }],
-      data: None,
-      url: None,
       children,
+      ..Self::new(NodeId::default(), name.into())
     }
   }
 }
@@ -98,8 +228,19 @@ impl Node {
 pub struct Version(u16);
 
 impl Version {
+  /// Initial version.
+  pub const V1: Self = Version(1);
+
+  /// Flattened text and string tree types.
+  pub const V2: Self = Version(2);
+
   pub const fn current() -> Self {
-    Version(1)
+    Self::V2
+  }
+
+  /// Check whether trees encoded with this version can be decoded.
+  pub fn is_known(&self) -> bool {
+    (Self::V1..=Self::current()).contains(self)
   }
 }
 
@@ -109,31 +250,103 @@ impl Default for Version {
   }
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub struct Text {
-  pub text: String,
+impl std::fmt::Display for Version {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    self.0.fmt(f)
+  }
 }
 
 #[cfg(test)]
 mod tests {
-  use super::Node;
+  use super::{Node, Tree, TreeType, Version};
+  use crate::node::NodeData;
+
+  const ID: &str = "0b9e6a04-3c3b-4b8e-a0d5-6f9e7fd4a1c2";
+
+  fn node(name: &str, is_expanded: bool) -> Node {
+    Node {
+      id: ID.parse().unwrap(),
+      ..Node::new_by_expand_state(name, is_expanded, Vec::new())
+    }
+  }
 
   #[test]
   fn serialize() {
-    let s = serde_json::to_string(&Node::new_by_expand_state("name", true, Vec::new()));
+    let s = serde_json::to_string(&node("name", true));
     assert_eq!(
       s.unwrap(),
-      r#"{"icon":"","is_expanded":true,"contents":[{"text":"name"}]}"#
+      format!(r#"{{"id":"{ID}","icon":"","is_expanded":true,"text":"name"}}"#)
     );
   }
 
   #[test]
   fn deserialize() {
-    let s = r#"{"icon":"","is_expanded":true,"contents":[{"text":"name"}]}"#;
-    let node = serde_json::from_str::<Node>(s);
+    let s = format!(r#"{{"id":"{ID}","icon":"","is_expanded":true,"text":"name"}}"#);
+    let node_ = serde_json::from_str::<Node>(&s);
+    assert_eq!(node_.unwrap(), node("name", true));
+  }
+
+  #[test]
+  fn deserialize_without_id() {
+    let s = r#"{"icon":"","is_expanded":true,"text":"name"}"#;
+    let a = serde_json::from_str::<Node>(s).unwrap();
+    let b = serde_json::from_str::<Node>(s).unwrap();
+    assert_ne!(a.id, b.id);
+  }
+
+  #[test]
+  fn serialize_attachments() {
+    let node = Node {
+      attachments: vec![
+        NodeData::file("/tmp/foo.md"),
+        NodeData::link("https://foo.bar"),
+      ],
+      ..node("name", false)
+    };
+    let s = serde_json::to_string(&node);
+    assert_eq!(
+      s.unwrap(),
+      format!(
+        r#"{{"id":"{ID}","icon":"","is_expanded":false,"text":"name","attachments":[{{"file":"/tmp/foo.md"}},{{"link":"https://foo.bar"}}]}}"#
+      )
+    );
+  }
+
+  #[test]
+  fn serialize_tree() {
+    let tree = Tree {
+      version: Version::current(),
+      ty: TreeType::Local,
+      node: node("name", false),
+    };
+    let s = serde_json::to_string(&tree);
     assert_eq!(
-      node.unwrap(),
-      Node::new_by_expand_state("name", true, Vec::new())
+      s.unwrap(),
+      format!(
+        r#"{{"version":2,"type":"local","id":"{ID}","icon":"","is_expanded":false,"text":"name"}}"#
+      )
     );
   }
+
+  #[test]
+  fn deserialize_tree() {
+    let s = format!(
+      r#"{{"version":2,"type":"root","id":"{ID}","icon":"","is_expanded":true,"text":"name"}}"#
+    );
+    let tree = serde_json::from_str::<Tree>(&s);
+    assert_eq!(
+      tree.unwrap(),
+      Tree {
+        version: Version::current(),
+        ty: TreeType::Root,
+        node: node("name", true),
+      }
+    );
+  }
+
+  #[test]
+  fn deserialize_unknown_version() {
+    let s = r#"{"version":42,"type":"root","icon":"","is_expanded":true,"text":"name"}"#;
+    assert!(serde_json::from_str::<Tree>(s).is_err());
+  }
 }
@@ -0,0 +1,185 @@
+//! Version 1 of the encoding.
+//!
+//! This version is only supported for reading; trees in this version are migrated to the current version when
+//! converted.
+
+use crate::node::{NodeData, NodeId};
+use serde::{de::Error as _, Deserialize};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TreeType {
+  /// A root.
+  Root = 0,
+
+  /// A local root.
+  Local = 1,
+}
+
+impl<'de> Deserialize<'de> for TreeType {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    match u8::deserialize(deserializer)? {
+      0 => Ok(TreeType::Root),
+      1 => Ok(TreeType::Local),
+      ty => Err(D::Error::custom(format!("unknown tree type: {ty}"))),
+    }
+  }
+}
+
+impl From<TreeType> for super::TreeType {
+  fn from(value: TreeType) -> Self {
+    match value {
+      TreeType::Root => super::TreeType::Root,
+      TreeType::Local => super::TreeType::Local,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct Tree {
+  /// Type of node.
+  #[serde(rename = "type")]
+  pub ty: TreeType,
+
+  /// A tree is also a node, so we flatten the content of a node when doing deser.
+  #[serde(flatten)]
+  pub node: Node,
+}
+
+impl From<Tree> for super::Tree {
+  fn from(value: Tree) -> Self {
+    super::Tree {
+      version: super::Version::current(),
+      ty: value.ty.into(),
+      node: value.node.into(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct Node {
+  /// Icon associated with this node.
+  #[serde(default)]
+  pub icon: String,
+
+  /// Whether the node is expanded or collapsed.
+  #[serde(default)]
+  pub is_expanded: bool,
+
+  /// Text associated with the node.
+  pub contents: Vec<Text>,
+
+  /// Data file associated with, if any.
+  pub data: Option<PathBuf>,
+
+  /// Link associated with, if any.
+  pub url: Option<String>,
+
+  /// Children nodes, if any.
+  #[serde(default)]
+  pub children: Vec<Node>,
+}
+
+impl From<Node> for super::Node {
+  fn from(mut value: Node) -> Self {
+    super::Node {
+      id: NodeId::new(),
+      icon: value.icon,
+      is_expanded: value.is_expanded,
+      is_clone: false,
+      text: value
+        .contents
+        .pop()
+        .map(|text| text.text)
+        .unwrap_or_default(),
+      created_at: None,
+      modified_at: None,
+      tags: Default::default(),
+      properties: Default::default(),
+      status: None,
+      body: None,
+      due: None,
+      scheduled: None,
+      attachments: value
+        .data
+        .map(NodeData::File)
+        .into_iter()
+        .chain(value.url.map(NodeData::Link))
+        .collect(),
+      children: value.children.into_iter().map(Into::into).collect(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct Text {
+  pub text: String,
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    encoding::{self, TreeType, Version},
+    node::NodeData,
+  };
+
+  const TREE: &str = include_str!("../../fixtures/v1/tree.json");
+
+  #[test]
+  fn migrate_tree() {
+    let tree = serde_json::from_str::<encoding::Tree>(TREE).unwrap();
+
+    assert_eq!(tree.version, Version::current());
+    assert_eq!(tree.ty, TreeType::Local);
+    assert_eq!(tree.node.text, "Project");
+    assert_eq!(tree.node.icon, " ");
+    assert!(tree.node.is_expanded);
+    assert_eq!(tree.node.children.len(), 3);
+
+    let tasks = &tree.node.children[0];
+    assert_eq!(tasks.text, "Tasks");
+    assert_eq!(tasks.children[0].text, "Fix the build");
+    assert_eq!(
+      tasks.children[0].attachments,
+      vec![NodeData::file(
+        "/home/user/.local/share/mind/data/2023612101010-Fix-the-build.md"
+      )]
+    );
+
+    let links = &tree.node.children[1];
+    assert_eq!(links.children[0].text, "Repository");
+    assert_eq!(
+      links.children[0].attachments,
+      vec![NodeData::link("https://github.com/phaazon/mind")]
+    );
+
+    assert_eq!(tree.node.children[2].text, "Ideas");
+    assert!(tree.node.children[2].children.is_empty());
+  }
+
+  #[test]
+  fn migrate_root_type() {
+    let s = r#"{"version":1,"type":0,"icon":"","is_expanded":false,"contents":[{"text":"Main"}]}"#;
+    let tree = serde_json::from_str::<encoding::Tree>(s).unwrap();
+    assert_eq!(tree.ty, TreeType::Root);
+    assert_eq!(tree.node.text, "Main");
+  }
+
+  #[test]
+  fn migrate_unversioned() {
+    let s = r#"{"type":0,"is_expanded":false,"contents":[{"text":"Main"}]}"#;
+    let tree = serde_json::from_str::<encoding::Tree>(s).unwrap();
+    assert_eq!(tree.version, Version::current());
+    assert_eq!(tree.node.text, "Main");
+  }
+
+  #[test]
+  fn migrated_tree_persists_current_version() {
+    let tree = serde_json::from_str::<encoding::Tree>(TREE).unwrap();
+    let reloaded = serde_json::from_str::<encoding::Tree>(&serde_json::to_string(&tree).unwrap());
+    assert_eq!(reloaded.unwrap(), tree);
+  }
+}
@@ -101,3 +101,47 @@ pub enum ForestError {
   #[error("cannot serialize forest: {0}")]
   CannotSerialize(serde_json::error::Error),
 }
+
+#[cfg(test)]
+mod tests {
+  use super::Forest;
+
+  const V1_FOREST: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/v1/forest.json");
+
+  #[test]
+  fn load_v1_forest() {
+    let forest = Forest::from_path(V1_FOREST).unwrap();
+
+    assert_eq!(forest.main_tree().root().name(), "Main");
+    assert_eq!(
+      forest.main_tree().root().paths("/", Default::default()),
+      vec!["/", "/Journal", "/Journal/2023", "/Inbox"]
+    );
+
+    let project = forest.cwd_tree("/home/user/projects/mind").unwrap();
+    assert_eq!(project.root().name(), "mind");
+    assert_eq!(
+      project.root().paths("", Default::default()),
+      vec!["", "/Backlog"]
+    );
+  }
+
+  #[test]
+  fn persist_migrated_v1_forest() {
+    let forest = Forest::from_path(V1_FOREST).unwrap();
+    let path = std::env::temp_dir().join(format!("mind-test-{}/forest.json", std::process::id()));
+
+    forest.persist(&path).unwrap();
+    let persisted: serde_json::Value =
+      serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+
+    assert_eq!(persisted["tree"]["version"], 2);
+    assert_eq!(persisted["tree"]["type"], "root");
+    assert_eq!(persisted["tree"]["text"], "Main");
+    assert_eq!(
+      persisted["projects"]["/home/user/projects/mind"]["children"][0]["text"],
+      "Backlog"
+    );
+  }
+}
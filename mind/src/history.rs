@@ -0,0 +1,557 @@
+//! Reversible operations on trees, and their history.
+//!
+//! Applying an [`Operation`] yields its inverse, which, once applied, reverts the operation. A [`History`] keeps track
+//! of the inverses of the operations applied to a tree, so that they can be undone and redone.
+
+use crate::node::{Node, NodeData, NodeError, SortKey, TaskStatus};
+
+/// Maximum number of operations that can be undone.
+const MAX_HISTORY_LEN: usize = 100;
+
+/// A reversible operation on a tree.
+#[derive(Clone, Debug)]
+pub enum Operation {
+  /// Insert a node as the child of `parent` at the given index.
+  ///
+  /// The node is inserted at the bottom if the index is past the last child.
+  Insert {
+    parent: Node,
+    index: usize,
+    node: Node,
+  },
+
+  /// Delete a node from its parent.
+  Delete { node: Node },
+
+  /// Move a node so that it ends up as the child of `parent` at the given index.
+  Move {
+    node: Node,
+    parent: Node,
+    index: usize,
+  },
+
+  /// Rename a node.
+  Rename { node: Node, name: String },
+
+  /// Change the icon of a node.
+  SetIcon { node: Node, icon: String },
+
+  /// Change the task status of a node.
+  SetStatus {
+    node: Node,
+    status: Option<TaskStatus>,
+  },
+
+  /// Attach data to a node.
+  Attach { node: Node, data: NodeData },
+
+  /// Replace all the attachments of a node.
+  SetAttachments {
+    node: Node,
+    attachments: Vec<NodeData>,
+  },
+
+  /// Reorder the children of a node; `children` must contain exactly its current children.
+  Reorder { node: Node, children: Vec<Node> },
+
+  /// Sort the children of a node, optionally in reverse order and recursively.
+  Sort {
+    node: Node,
+    key: SortKey,
+    reverse: bool,
+    recursive: bool,
+  },
+
+  /// Several operations, applied in order as a whole.
+  Group(Vec<Operation>),
+}
+
+impl Operation {
+  /// Insert a node as the first child of `parent`.
+  pub fn insert_top(parent: Node, node: Node) -> Self {
+    Operation::Insert {
+      parent,
+      index: 0,
+      node,
+    }
+  }
+
+  /// Insert a node as the last child of `parent`.
+  pub fn insert_bottom(parent: Node, node: Node) -> Self {
+    let index = parent.children().into_iter().count();
+    Operation::Insert {
+      parent,
+      index,
+      node,
+    }
+  }
+
+  /// Insert a node right before `sibling`.
+  pub fn insert_before(sibling: &Node, node: Node) -> Result<Self, NodeError> {
+    Ok(Operation::Insert {
+      index: sibling.get_index_from_parent()?,
+      parent: sibling.parent()?,
+      node,
+    })
+  }
+
+  /// Insert a node right after `sibling`.
+  pub fn insert_after(sibling: &Node, node: Node) -> Result<Self, NodeError> {
+    Ok(Operation::Insert {
+      index: sibling.get_index_from_parent()? + 1,
+      parent: sibling.parent()?,
+      node,
+    })
+  }
+
+  /// Move a node at the top of `parent`.
+  pub fn move_top(parent: Node, node: Node) -> Self {
+    Operation::Move {
+      node,
+      parent,
+      index: 0,
+    }
+  }
+
+  /// Move a node at the bottom of `parent`.
+  pub fn move_bottom(parent: Node, node: Node) -> Self {
+    let index = parent.children().into_iter().count();
+    Operation::Move {
+      node,
+      parent,
+      index,
+    }
+  }
+
+  /// Move a node right before `sibling`.
+  pub fn move_before(sibling: &Node, node: Node) -> Result<Self, NodeError> {
+    let parent = sibling.parent()?;
+    let mut index = sibling.get_index_from_parent()?;
+
+    // the node is removed before being moved, which shifts the siblings after it
+    if matches!(node.parent(), Ok(node_parent) if node_parent == parent)
+      && node.get_index_from_parent()? < index
+    {
+      index -= 1;
+    }
+
+    Ok(Operation::Move {
+      node,
+      parent,
+      index,
+    })
+  }
+
+  /// Move a node right after `sibling`.
+  pub fn move_after(sibling: &Node, node: Node) -> Result<Self, NodeError> {
+    let parent = sibling.parent()?;
+    let mut index = sibling.get_index_from_parent()? + 1;
+
+    // the node is removed before being moved, which shifts the siblings after it
+    if matches!(node.parent(), Ok(node_parent) if node_parent == parent)
+      && node.get_index_from_parent()? < index
+    {
+      index -= 1;
+    }
+
+    Ok(Operation::Move {
+      node,
+      parent,
+      index,
+    })
+  }
+
+  /// Apply the operation, returning its inverse.
+  ///
+  /// If the operation fails, the tree is left untouched.
+  pub fn apply(self) -> Result<Operation, NodeError> {
+    match self {
+      Operation::Insert {
+        parent,
+        index,
+        node,
+      } => {
+        parent.insert_at(index, node.clone())?;
+        Ok(Operation::Delete { node })
+      }
+
+      Operation::Delete { node } => {
+        let parent = node.parent()?;
+        let index = node.get_index_from_parent()?;
+        parent.delete(node.clone())?;
+
+        Ok(Operation::Insert {
+          parent,
+          index,
+          node,
+        })
+      }
+
+      Operation::Move {
+        node,
+        parent,
+        index,
+      } => {
+        let old_parent = parent.check_move(&node)?;
+        let old_index = node.get_index_from_parent()?;
+        old_parent.delete(node.clone())?;
+
+        if let Err(err) = parent.insert_at(index, node.clone()) {
+          old_parent.insert_at(old_index, node)?;
+          return Err(err);
+        }
+
+        Ok(Operation::Move {
+          node,
+          parent: old_parent,
+          index: old_index,
+        })
+      }
+
+      Operation::Rename { node, name } => {
+        let old_name = node.name();
+        node.set_name(name)?;
+
+        Ok(Operation::Rename {
+          node,
+          name: old_name,
+        })
+      }
+
+      Operation::SetIcon { node, icon } => {
+        let old_icon = node.raw_icon();
+        node.set_icon(icon);
+
+        Ok(Operation::SetIcon {
+          node,
+          icon: old_icon,
+        })
+      }
+
+      Operation::SetStatus { node, status } => {
+        let old_status = node.status();
+        node.set_status(status);
+
+        Ok(Operation::SetStatus {
+          node,
+          status: old_status,
+        })
+      }
+
+      Operation::Attach { node, data } => {
+        let attachments = node.attachments();
+        node.add_attachment(data)?;
+
+        Ok(Operation::SetAttachments { node, attachments })
+      }
+
+      Operation::SetAttachments { node, attachments } => {
+        let old_attachments = node.attachments();
+        node.set_attachments(attachments);
+
+        Ok(Operation::SetAttachments {
+          node,
+          attachments: old_attachments,
+        })
+      }
+
+      Operation::Reorder { node, children } => {
+        let old_children = node.children().into_iter().cloned().collect();
+        node.reorder_children(children)?;
+
+        Ok(Operation::Reorder {
+          node,
+          children: old_children,
+        })
+      }
+
+      Operation::Sort {
+        node,
+        key,
+        reverse,
+        recursive,
+      } => {
+        let nodes = if recursive {
+          node.subtree()
+        } else {
+          vec![node.clone()]
+        };
+        let inverses = nodes
+          .into_iter()
+          .filter(|node| node.has_children())
+          .map(|node| {
+            let children = node.children().into_iter().cloned().collect();
+            Operation::Reorder { node, children }
+          })
+          .collect();
+        node.sort_children(key, reverse, recursive);
+
+        Ok(Operation::Group(inverses))
+      }
+
+      Operation::Group(operations) => {
+        let mut inverses = Vec::with_capacity(operations.len());
+
+        for operation in operations {
+          match operation.apply() {
+            Ok(inverse) => inverses.push(inverse),
+
+            // revert what was already applied
+            Err(err) => {
+              for inverse in inverses.into_iter().rev() {
+                inverse.apply()?;
+              }
+
+              return Err(err);
+            }
+          }
+        }
+
+        inverses.reverse();
+        Ok(Operation::Group(inverses))
+      }
+    }
+  }
+
+  /// Node most relevant to the operation, e.g. to put the focus on once it is applied.
+  ///
+  /// This is the node the operation is about, or its parent for deletions; it must then be computed before the
+  /// operation is applied.
+  pub fn target(&self) -> Option<Node> {
+    match self {
+      Operation::Insert { node, .. }
+      | Operation::Move { node, .. }
+      | Operation::Rename { node, .. }
+      | Operation::SetIcon { node, .. }
+      | Operation::SetStatus { node, .. }
+      | Operation::Attach { node, .. }
+      | Operation::SetAttachments { node, .. }
+      | Operation::Reorder { node, .. }
+      | Operation::Sort { node, .. } => Some(node.clone()),
+      Operation::Delete { node } => node.parent().ok(),
+      Operation::Group(operations) => operations.last().and_then(Operation::target),
+    }
+  }
+}
+
+/// History of the operations applied to a tree.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+  /// Inverses of the applied operations, the most recent last.
+  undo: Vec<Operation>,
+
+  /// Inverses of the undone operations, the most recent last.
+  redo: Vec<Operation>,
+}
+
+impl History {
+  /// Apply an operation and record it.
+  ///
+  /// Operations that were undone cannot be redone anymore afterwards.
+  pub fn apply(&mut self, operation: Operation) -> Result<(), NodeError> {
+    let inverse = operation.apply()?;
+    self.record(inverse);
+    Ok(())
+  }
+
+  /// Record the inverse of an operation that was already applied.
+  pub(crate) fn record(&mut self, inverse: Operation) {
+    if self.undo.len() == MAX_HISTORY_LEN {
+      self.undo.remove(0);
+    }
+
+    self.undo.push(inverse);
+    self.redo.clear();
+  }
+
+  /// Undo the last applied operation.
+  ///
+  /// Returns the [target](Operation::target) of the undo, if any; `None` means there was nothing to undo.
+  pub fn undo(&mut self) -> Result<Option<Node>, NodeError> {
+    Self::revert(&mut self.undo, &mut self.redo)
+  }
+
+  /// Redo the last undone operation.
+  ///
+  /// Returns the [target](Operation::target) of the redo, if any; `None` means there was nothing to redo.
+  pub fn redo(&mut self) -> Result<Option<Node>, NodeError> {
+    Self::revert(&mut self.redo, &mut self.undo)
+  }
+
+  pub fn can_undo(&self) -> bool {
+    !self.undo.is_empty()
+  }
+
+  pub fn can_redo(&self) -> bool {
+    !self.redo.is_empty()
+  }
+
+  fn revert(from: &mut Vec<Operation>, to: &mut Vec<Operation>) -> Result<Option<Node>, NodeError> {
+    let operation = match from.pop() {
+      Some(operation) => operation,
+      None => return Ok(None),
+    };
+
+    let target = operation.target();
+    match operation.clone().apply() {
+      Ok(inverse) => {
+        to.push(inverse);
+        Ok(target)
+      }
+
+      // keep the operation around so that the history stays consistent
+      Err(err) => {
+        from.push(operation);
+        Err(err)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Operation;
+  use crate::node::{Node, NodeData, NodeFilter, TaskStatus, Tree};
+
+  fn paths(tree: &Tree) -> Vec<String> {
+    tree.root().paths("/", NodeFilter::default())
+  }
+
+  #[test]
+  fn undo_redo() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    let x = Node::new("x", "");
+
+    tree
+      .apply(Operation::insert_bottom(root.clone(), a.clone()))
+      .unwrap();
+    tree
+      .apply(Operation::insert_after(&a, b.clone()).unwrap())
+      .unwrap();
+    tree
+      .apply(Operation::insert_top(a.clone(), x.clone()))
+      .unwrap();
+    tree
+      .apply(Operation::Rename {
+        node: x.clone(),
+        name: "y".to_owned(),
+      })
+      .unwrap();
+    assert_eq!(paths(&tree), vec!["/", "/a", "/a/y", "/b"]);
+
+    // deleting a subtree can be undone
+    tree.apply(Operation::Delete { node: a.clone() }).unwrap();
+    assert_eq!(paths(&tree), vec!["/", "/b"]);
+    assert_eq!(tree.undo().unwrap(), Some(a.clone()));
+    assert_eq!(paths(&tree), vec!["/", "/a", "/a/y", "/b"]);
+
+    assert_eq!(tree.undo().unwrap(), Some(x.clone()));
+    assert_eq!(paths(&tree), vec!["/", "/a", "/a/x", "/b"]);
+
+    assert_eq!(tree.redo().unwrap(), Some(x.clone()));
+    assert_eq!(tree.redo().unwrap(), Some(root.clone()));
+    assert_eq!(tree.redo().unwrap(), None);
+    assert_eq!(paths(&tree), vec!["/", "/b"]);
+
+    // applying an operation forgets about undone operations
+    tree.undo().unwrap();
+    tree
+      .apply(Operation::SetStatus {
+        node: b.clone(),
+        status: Some(TaskStatus::Todo),
+      })
+      .unwrap();
+    assert_eq!(tree.redo().unwrap(), None);
+
+    for _ in 0..5 {
+      tree.undo().unwrap();
+    }
+    assert_eq!(tree.undo().unwrap(), None);
+    assert_eq!(paths(&tree), vec!["/"]);
+    assert_eq!(b.status(), None);
+  }
+
+  #[test]
+  fn moves_and_attachments() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    let c = Node::new("c", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(b.clone());
+    root.insert_bottom(c.clone());
+
+    tree
+      .apply(Operation::Move {
+        node: a.clone(),
+        parent: root.clone(),
+        index: 2,
+      })
+      .unwrap();
+    assert_eq!(paths(&tree), vec!["/", "/b", "/c", "/a"]);
+
+    tree
+      .apply(Operation::Move {
+        node: c.clone(),
+        parent: b.clone(),
+        index: 0,
+      })
+      .unwrap();
+    assert_eq!(paths(&tree), vec!["/", "/b", "/b/c", "/a"]);
+
+    tree
+      .apply(Operation::Attach {
+        node: a.clone(),
+        data: NodeData::link("https://foo.bar"),
+      })
+      .unwrap();
+    tree.undo().unwrap();
+    assert!(a.attachments().is_empty());
+
+    tree.undo().unwrap();
+    tree.undo().unwrap();
+    assert_eq!(paths(&tree), vec!["/", "/a", "/b", "/c"]);
+  }
+
+  #[test]
+  fn icon_trailing_space() {
+    let tree = Tree::new("root", "");
+    let a = Node::new("a", "x ");
+    tree.root().insert_bottom(a.clone());
+
+    tree
+      .apply(Operation::SetIcon {
+        node: a.clone(),
+        icon: "y".to_owned(),
+      })
+      .unwrap();
+    assert_eq!(a.icon(), "y ");
+
+    tree.undo().unwrap();
+    assert_eq!(a.icon(), "x  ");
+    tree.redo().unwrap();
+    assert_eq!(a.icon(), "y ");
+  }
+
+  #[test]
+  fn failed_group() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+
+    let group = Operation::Group(vec![
+      Operation::insert_bottom(root.clone(), a.clone()),
+      Operation::Rename {
+        node: a.clone(),
+        name: " ".to_owned(),
+      },
+    ]);
+    assert!(tree.apply(group).is_err());
+    assert_eq!(paths(&tree), vec!["/"]);
+    assert_eq!(tree.undo().unwrap(), None);
+  }
+}
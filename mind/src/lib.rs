@@ -1,5 +1,13 @@
+pub mod agenda;
 pub mod config;
 pub mod data_file;
+pub mod date;
+pub mod diff;
+pub mod doctor;
 pub mod encoding;
 pub mod forest;
+pub mod history;
+pub mod merge;
 pub mod node;
+pub mod search;
+pub mod stats;
@@ -0,0 +1,745 @@
+//! Three-way merge of trees.
+//!
+//! Nodes are matched by [`NodeId`] between a common ancestor (the _base_) and two descendants (_ours_ and _theirs_).
+//! Changes made on a single side are merged automatically; changes made differently on both sides are conflicts, which
+//! are kept as marker nodes in the merged tree so that they can be resolved by hand.
+
+use crate::node::{Node, NodeData, NodeId, Tree};
+use std::{
+  collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+  fmt,
+};
+
+/// Prefix of the name of the nodes marking conflicts in merged trees.
+pub const CONFLICT_MARKER: &str = "<<<<<<< conflict: ";
+
+/// Kind of conflict.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConflictKind {
+  /// Both sides changed a field of the node differently.
+  Field {
+    field: String,
+    ours: String,
+    theirs: String,
+  },
+
+  /// Both sides moved the node to different parents, given by path.
+  Moved { ours: String, theirs: String },
+
+  /// The node was deleted on our side but modified on theirs; it is kept.
+  DeletedByOurs,
+
+  /// The node was modified on our side but deleted on theirs; it is kept.
+  DeletedByTheirs,
+}
+
+impl fmt::Display for ConflictKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ConflictKind::Field { field, .. } => f.write_str(field),
+      ConflictKind::Moved { .. } => f.write_str("parent"),
+      ConflictKind::DeletedByOurs => f.write_str("deleted by ours"),
+      ConflictKind::DeletedByTheirs => f.write_str("deleted by theirs"),
+    }
+  }
+}
+
+/// A conflict left in a merged tree.
+#[derive(Clone, Debug)]
+pub struct Conflict {
+  /// Conflicting node, in the merged tree.
+  pub node: Node,
+  pub kind: ConflictKind,
+}
+
+/// Result of a merge.
+#[derive(Debug)]
+pub struct Merge {
+  pub tree: Tree,
+
+  /// Conflicts that need to be resolved by hand, in the order they were found.
+  pub conflicts: Vec<Conflict>,
+}
+
+/// Merge the changes made from `base` in `ours` and in `theirs`.
+///
+/// The merged tree starts as a copy of `ours`, on which the changes made in `theirs` are applied:
+///
+/// - Names, icons, statuses, bodies and dates are taken from the side that changed them.
+/// - Tags and attachments added or removed on either side are added or removed; properties are merged key by key.
+/// - Nodes moved to another parent on either side are moved; reordering siblings is not merged, though.
+/// - Nodes added on either side are added, after the same sibling as on their side if possible.
+/// - Nodes deleted on either side are deleted, unless something changed in their subtree on the other side.
+///
+/// Every change made differently on both sides is a [`Conflict`]: the merged tree keeps the version of `ours`, and a
+/// marker node, named after [`CONFLICT_MARKER`], is inserted at the top of the conflicting node, with the version of
+/// each side as children.
+///
+/// The three trees must have been persisted with the IDs of their nodes; see [`has_node_ids`].
+pub fn merge(base: &Tree, ours: &Tree, theirs: &Tree) -> Merge {
+  let mut merger = Merger {
+    base: nodes_by_id(&base.root()),
+    ours: nodes_by_id(&ours.root()),
+    merged: Tree::from_encoding(ours.into_encoding()),
+    kept: HashSet::new(),
+    conflicts: Vec::new(),
+  };
+
+  for node in unique_nodes(&theirs.root()) {
+    merger.merge_theirs(&node);
+  }
+
+  let theirs = nodes_by_id(&theirs.root());
+  for node in unique_nodes(&base.root()) {
+    if !theirs.contains_key(&node.id()) {
+      merger.delete_theirs(&node);
+    }
+  }
+
+  for conflict in &merger.conflicts {
+    insert_marker(conflict);
+  }
+
+  Merge {
+    tree: merger.merged,
+    conflicts: merger.conflicts,
+  }
+}
+
+/// Check whether all the nodes of an encoded tree have a persisted ID.
+///
+/// Nodes persisted before IDs existed get a new, random one every time they are loaded, so they cannot be matched
+/// between trees by [`merge`]; this check works on the JSON representation of trees for that reason.
+pub fn has_node_ids(tree: &serde_json::Value) -> bool {
+  tree.get("id").is_some()
+    && tree
+      .get("children")
+      .and_then(|children| children.as_array())
+      .map_or(true, |children| children.iter().all(has_node_ids))
+}
+
+/// Outcome of merging a value that might have changed on both sides.
+enum Outcome {
+  Ours,
+  Theirs,
+  Conflict,
+}
+
+impl Outcome {
+  fn new<T: PartialEq>(base: &T, ours: &T, theirs: &T) -> Self {
+    if theirs == base || ours == theirs {
+      Outcome::Ours
+    } else if ours == base {
+      Outcome::Theirs
+    } else {
+      Outcome::Conflict
+    }
+  }
+}
+
+/// Everything a merge looks at in a node.
+#[derive(PartialEq)]
+struct Snapshot {
+  parent: Option<NodeId>,
+  name: String,
+  icon: String,
+  tags: BTreeSet<String>,
+  properties: BTreeMap<String, String>,
+  attachments: Vec<NodeData>,
+  status: Option<String>,
+  body: Option<String>,
+  due: Option<String>,
+  scheduled: Option<String>,
+}
+
+impl Snapshot {
+  fn new(node: &Node) -> Self {
+    Self {
+      parent: node.parent().ok().map(|parent| parent.id()),
+      name: node.name(),
+      icon: node.raw_icon(),
+      tags: node.tags(),
+      properties: node.properties(),
+      attachments: node.attachments(),
+      status: node.status().map(|status| status.to_string()),
+      body: node.body(),
+      due: node.due().map(|date| date.to_string()),
+      scheduled: node.scheduled().map(|date| date.to_string()),
+    }
+  }
+}
+
+struct Merger {
+  base: HashMap<NodeId, Node>,
+  ours: HashMap<NodeId, Node>,
+  merged: Tree,
+
+  /// Nodes deleted on a side but kept because of changes made on the other side.
+  ///
+  /// Only the topmost of them are conflicts; their descendants are kept along.
+  kept: HashSet<NodeId>,
+
+  conflicts: Vec<Conflict>,
+}
+
+impl Merger {
+  /// Merge a node of theirs in the merged tree.
+  fn merge_theirs(&mut self, theirs: &Node) {
+    let id = theirs.id();
+
+    match (self.base.get(&id).cloned(), self.ours.contains_key(&id)) {
+      (Some(base), true) => self.merge_node(&base, theirs),
+
+      // deleted by ours; restored if needed by theirs
+      (Some(_), false) => {
+        let kept_parent = self.has_kept_parent(theirs);
+
+        if kept_parent || self.changes_subtree(theirs) {
+          let node = theirs.detached_copy();
+          self.place(&node, theirs);
+          self.kept.insert(id);
+
+          if !kept_parent {
+            self.conflicts.push(Conflict {
+              node,
+              kind: ConflictKind::DeletedByOurs,
+            });
+          }
+        }
+      }
+
+      // added by theirs
+      (None, false) => self.place(&theirs.detached_copy(), theirs),
+
+      // added on both sides with the same ID; nothing to merge
+      (None, true) => (),
+    }
+  }
+
+  /// Delete a node deleted by theirs from the merged tree, unless we changed it.
+  fn delete_theirs(&mut self, base: &Node) {
+    let (Some(ours), Some(merged)) = (
+      self.ours.get(&base.id()),
+      self.merged.get_node_by_id(base.id()),
+    ) else {
+      return;
+    };
+
+    if self.changes_subtree(ours) {
+      self.kept.insert(base.id());
+
+      if !self.has_kept_parent(base) {
+        self.conflicts.push(Conflict {
+          node: merged,
+          kind: ConflictKind::DeletedByTheirs,
+        });
+      }
+    } else if let Ok(parent) = merged.parent() {
+      let _ = parent.delete(merged);
+    }
+  }
+
+  /// Merge a node existing on both sides.
+  fn merge_node(&mut self, base: &Node, theirs_node: &Node) {
+    let (Some(merged), Some(ours)) = (
+      self.merged.get_node_by_id(base.id()),
+      self.ours.get(&base.id()).cloned(),
+    ) else {
+      return;
+    };
+
+    let base = Snapshot::new(base);
+    let ours = Snapshot::new(&ours);
+    let theirs = Snapshot::new(theirs_node);
+
+    self.merge_field(
+      &merged,
+      "name",
+      &base.name,
+      &ours.name,
+      &theirs.name,
+      |node, name| {
+        let _ = node.set_name(name);
+      },
+    );
+    self.merge_field(
+      &merged,
+      "icon",
+      &base.icon,
+      &ours.icon,
+      &theirs.icon,
+      |node, icon| {
+        node.set_icon(icon);
+      },
+    );
+    self.merge_field(
+      &merged,
+      "body",
+      &base.body,
+      &ours.body,
+      &theirs.body,
+      |node, body| {
+        node.set_body(body.as_ref());
+      },
+    );
+
+    // statuses and dates are compared as strings, so the actual values are taken from theirs
+    self.merge_field(
+      &merged,
+      "status",
+      &base.status,
+      &ours.status,
+      &theirs.status,
+      |node, _| node.set_status(theirs_node.status()),
+    );
+    self.merge_field(
+      &merged,
+      "due",
+      &base.due,
+      &ours.due,
+      &theirs.due,
+      |node, _| node.set_due(theirs_node.due()),
+    );
+    self.merge_field(
+      &merged,
+      "scheduled",
+      &base.scheduled,
+      &ours.scheduled,
+      &theirs.scheduled,
+      |node, _| node.set_scheduled(theirs_node.scheduled()),
+    );
+
+    for tag in theirs.tags.difference(&base.tags) {
+      let _ = merged.add_tag(tag);
+    }
+
+    for tag in base.tags.difference(&theirs.tags) {
+      merged.remove_tag(tag);
+    }
+
+    let keys: BTreeSet<_> = base
+      .properties
+      .keys()
+      .chain(ours.properties.keys())
+      .chain(theirs.properties.keys())
+      .collect();
+    for key in keys {
+      self.merge_field(
+        &merged,
+        &format!("property {key}"),
+        &base.properties.get(key),
+        &ours.properties.get(key),
+        &theirs.properties.get(key),
+        |node, value| match value {
+          Some(value) => {
+            let _ = node.set_property(key, value.as_str());
+          }
+          None => {
+            node.unset_property(key);
+          }
+        },
+      );
+    }
+
+    if base.attachments != theirs.attachments {
+      let mut attachments: Vec<_> = ours
+        .attachments
+        .into_iter()
+        .filter(|data| !base.attachments.contains(data) || theirs.attachments.contains(data))
+        .collect();
+      for data in &theirs.attachments {
+        if !base.attachments.contains(data) && !attachments.contains(data) {
+          attachments.push(data.clone());
+        }
+      }
+
+      merged.set_attachments(attachments);
+    }
+
+    match Outcome::new(&base.parent, &ours.parent, &theirs.parent) {
+      Outcome::Ours => (),
+      Outcome::Theirs => self.move_theirs(&merged, theirs_node),
+      Outcome::Conflict => self.conflict_moved(&merged, theirs_node),
+    }
+  }
+
+  /// Merge a field of a node, applying the value of theirs with `set` if only they changed it.
+  fn merge_field<T: PartialEq + Show>(
+    &mut self,
+    merged: &Node,
+    field: &str,
+    base: &T,
+    ours: &T,
+    theirs: &T,
+    set: impl FnOnce(&Node, &T),
+  ) {
+    match Outcome::new(base, ours, theirs) {
+      Outcome::Ours => (),
+      Outcome::Theirs => set(merged, theirs),
+      Outcome::Conflict => self.conflicts.push(Conflict {
+        node: merged.clone(),
+        kind: ConflictKind::Field {
+          field: field.to_owned(),
+          ours: ours.show(),
+          theirs: theirs.show(),
+        },
+      }),
+    }
+  }
+
+  /// Move a node of the merged tree to where theirs is.
+  fn move_theirs(&mut self, merged: &Node, theirs: &Node) {
+    let parent = theirs
+      .parent()
+      .ok()
+      .and_then(|parent| self.merged.get_node_by_id(parent.id()));
+
+    // the new parent might have been deleted, or moved under the node by us
+    let Some(parent) = parent.filter(|parent| parent != merged && !merged.is_ancestor_of(parent))
+    else {
+      return self.conflict_moved(merged, theirs);
+    };
+
+    if let Ok(old_parent) = merged.parent() {
+      if old_parent.delete(merged.clone()).is_ok() {
+        self.insert(&parent, merged, theirs);
+      }
+    }
+  }
+
+  fn conflict_moved(&mut self, merged: &Node, theirs: &Node) {
+    let ours = self
+      .ours
+      .get(&merged.id())
+      .and_then(|ours| ours.parent().ok())
+      .map(|parent| parent.path());
+    let theirs = theirs.parent().ok().map(|parent| parent.path());
+
+    self.conflicts.push(Conflict {
+      node: merged.clone(),
+      kind: ConflictKind::Moved {
+        ours: ours.show(),
+        theirs: theirs.show(),
+      },
+    });
+  }
+
+  /// Place a new node in the merged tree where theirs is, or at the top of the root if its parent doesn’t exist.
+  fn place(&self, node: &Node, theirs: &Node) {
+    let parent = theirs
+      .parent()
+      .ok()
+      .and_then(|parent| self.merged.get_node_by_id(parent.id()))
+      .unwrap_or_else(|| self.merged.root());
+
+    self.insert(&parent, node, theirs);
+  }
+
+  /// Insert a node in `parent`, after the same sibling as theirs if possible, or at the top otherwise.
+  fn insert(&self, parent: &Node, node: &Node, theirs: &Node) {
+    let prev = theirs
+      .prev()
+      .and_then(|prev| self.merged.get_node_by_id(prev.id()))
+      .filter(|prev| matches!(prev.parent(), Ok(prev_parent) if &prev_parent == parent));
+
+    match prev {
+      Some(prev) => {
+        let _ = prev.insert_after(node.clone());
+      }
+      None => parent.insert_top(node.clone()),
+    }
+  }
+
+  fn has_kept_parent(&self, node: &Node) -> bool {
+    matches!(node.parent(), Ok(parent) if self.kept.contains(&parent.id()))
+  }
+
+  /// Check whether a side changed anything in the subtree of a node.
+  fn changes_subtree(&self, node: &Node) -> bool {
+    node.subtree().iter().any(|node| {
+      self
+        .base
+        .get(&node.id())
+        .map_or(true, |base| Snapshot::new(base) != Snapshot::new(node))
+    })
+  }
+}
+
+/// Values that can be shown in conflict markers.
+trait Show {
+  fn show(&self) -> String;
+}
+
+impl Show for String {
+  fn show(&self) -> String {
+    // names are single-line
+    self.lines().collect::<Vec<_>>().join(" ")
+  }
+}
+
+impl<T: Show> Show for Option<T> {
+  fn show(&self) -> String {
+    self
+      .as_ref()
+      .map(Show::show)
+      .unwrap_or_else(|| "none".to_owned())
+  }
+}
+
+impl<T: Show> Show for &T {
+  fn show(&self) -> String {
+    (*self).show()
+  }
+}
+
+fn insert_marker(conflict: &Conflict) {
+  let marker = Node::new(format!("{CONFLICT_MARKER}{}", conflict.kind), "");
+
+  if let ConflictKind::Field { ours, theirs, .. } | ConflictKind::Moved { ours, theirs } =
+    &conflict.kind
+  {
+    marker.insert_bottom(Node::new(format!("ours: {ours}"), ""));
+    marker.insert_bottom(Node::new(format!("theirs: {theirs}"), ""));
+  }
+
+  conflict.node.insert_top(marker);
+}
+
+/// Nodes of a subtree, in order and without duplicate clones.
+fn unique_nodes(root: &Node) -> Vec<Node> {
+  let mut seen = HashSet::new();
+  root
+    .subtree()
+    .into_iter()
+    .filter(|node| seen.insert(node.id()))
+    .collect()
+}
+
+fn nodes_by_id(root: &Node) -> HashMap<NodeId, Node> {
+  unique_nodes(root)
+    .into_iter()
+    .map(|node| (node.id(), node))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{has_node_ids, merge, ConflictKind, CONFLICT_MARKER};
+  use crate::node::{Node, NodeData, NodeFilter, Tree};
+
+  /// Copy a tree, keeping the IDs of its nodes.
+  fn copy(tree: &Tree) -> Tree {
+    Tree::from_encoding(tree.into_encoding())
+  }
+
+  /// Same node in another tree.
+  fn get(tree: &Tree, node: &Node) -> Node {
+    tree.get_node_by_id(node.id()).unwrap()
+  }
+
+  fn base() -> (Tree, [Node; 4]) {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    let c = Node::new("c", "");
+    let d = Node::new("d", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(b.clone());
+    a.insert_bottom(c.clone());
+    a.insert_bottom(d.clone());
+    (tree, [a, b, c, d])
+  }
+
+  #[test]
+  fn independent_changes() {
+    let (base, [a, b, c, d]) = base();
+    let ours = copy(&base);
+    let theirs = copy(&base);
+
+    get(&ours, &a).set_name("A").unwrap();
+    get(&ours, &b).insert_bottom(Node::new("x", ""));
+    get(&ours, &d).add_tag("ours").unwrap();
+    get(&ours, &d)
+      .add_attachment(NodeData::link("https://ours"))
+      .unwrap();
+
+    get(&theirs, &a).set_icon("*");
+    get(&theirs, &b).move_bottom(get(&theirs, &c)).unwrap();
+    get(&theirs, &d).insert_bottom(Node::new("y", ""));
+    get(&theirs, &d).add_tag("theirs").unwrap();
+    get(&theirs, &d)
+      .add_attachment(NodeData::link("https://theirs"))
+      .unwrap();
+    get(&theirs, &d).set_property("k", "v").unwrap();
+
+    let merge = merge(&base, &ours, &theirs);
+    assert!(merge.conflicts.is_empty());
+    assert_eq!(
+      merge.tree.root().paths("/", NodeFilter::default()),
+      ["/", "/A", "/A/d", "/A/d/y", "/b", "/b/c", "/b/x"]
+    );
+
+    let merged_a = get(&merge.tree, &a);
+    assert_eq!(merged_a.icon(), "* ");
+    let merged_d = get(&merge.tree, &d);
+    assert_eq!(
+      merged_d.tags().into_iter().collect::<Vec<_>>(),
+      ["ours", "theirs"]
+    );
+    assert_eq!(
+      merged_d.attachments(),
+      [
+        NodeData::link("https://ours"),
+        NodeData::link("https://theirs")
+      ]
+    );
+    assert_eq!(merged_d.property("k").as_deref(), Some("v"));
+
+    // the inputs are left untouched
+    assert_eq!(get(&ours, &a).icon(), "");
+  }
+
+  #[test]
+  fn icon_trailing_space() {
+    let (base, [a, b, ..]) = base();
+    a.set_icon("*");
+    let ours = copy(&base);
+    let theirs = copy(&base);
+
+    // trailing spaces are meaningful for some icons, so changing only them is a change
+    get(&theirs, &a).set_icon("* ");
+    get(&theirs, &b).set_icon("x ");
+
+    let merge = merge(&base, &ours, &theirs);
+    assert!(merge.conflicts.is_empty());
+    assert_eq!(get(&merge.tree, &a).icon(), "*  ");
+    assert_eq!(get(&merge.tree, &b).icon(), "x  ");
+  }
+
+  #[test]
+  fn deletions() {
+    let (base, [a, b, c, d]) = base();
+    let ours = copy(&base);
+    let theirs = copy(&base);
+
+    // deleted on one side only
+    get(&ours, &b)
+      .parent()
+      .unwrap()
+      .delete(get(&ours, &b))
+      .unwrap();
+    get(&theirs, &c)
+      .parent()
+      .unwrap()
+      .delete(get(&theirs, &c))
+      .unwrap();
+
+    // deleted by theirs, but modified by ours
+    get(&ours, &d).set_name("D").unwrap();
+    get(&theirs, &a)
+      .parent()
+      .unwrap()
+      .delete(get(&theirs, &a))
+      .unwrap();
+
+    let merge1 = merge(&base, &ours, &theirs);
+    let kinds: Vec<_> = merge1.conflicts.iter().map(|c| c.kind.clone()).collect();
+    assert_eq!(kinds, [ConflictKind::DeletedByTheirs]);
+    assert_eq!(merge1.conflicts[0].node, get(&merge1.tree, &a));
+    assert_eq!(
+      merge1.tree.root().paths("/", NodeFilter::default()),
+      [
+        "/".to_owned(),
+        "/a".to_owned(),
+        format!("/a/{CONFLICT_MARKER}deleted by theirs"),
+        "/a/D".to_owned(),
+      ]
+    );
+
+    // deleted by ours, but theirs added a child to it
+    get(&theirs, &b).insert_bottom(Node::new("z", ""));
+
+    let merge2 = merge(&base, &ours, &theirs);
+    let kinds: Vec<_> = merge2.conflicts.iter().map(|c| c.kind.clone()).collect();
+    assert_eq!(
+      kinds,
+      [ConflictKind::DeletedByOurs, ConflictKind::DeletedByTheirs]
+    );
+    assert_eq!(
+      merge2.tree.root().paths("/", NodeFilter::default()),
+      [
+        "/".to_owned(),
+        "/b".to_owned(),
+        format!("/b/{CONFLICT_MARKER}deleted by ours"),
+        "/b/z".to_owned(),
+        "/a".to_owned(),
+        format!("/a/{CONFLICT_MARKER}deleted by theirs"),
+        "/a/D".to_owned(),
+      ]
+    );
+  }
+
+  #[test]
+  fn conflicts() {
+    let (base, [a, b, c, _]) = base();
+    let ours = copy(&base);
+    let theirs = copy(&base);
+
+    get(&ours, &a).set_name("ours").unwrap();
+    get(&theirs, &a).set_name("theirs").unwrap();
+
+    // moving a node under another one that is moved under the first one
+    get(&ours, &b).move_bottom(get(&ours, &c)).unwrap();
+    get(&theirs, &c).move_bottom(get(&theirs, &b)).unwrap();
+
+    let merge = merge(&base, &ours, &theirs);
+    let kinds: Vec<_> = merge.conflicts.iter().map(|c| c.kind.clone()).collect();
+    assert_eq!(
+      kinds,
+      [
+        ConflictKind::Field {
+          field: "name".to_owned(),
+          ours: "ours".to_owned(),
+          theirs: "theirs".to_owned(),
+        },
+        ConflictKind::Moved {
+          ours: "/".to_owned(),
+          theirs: "/theirs/c".to_owned(),
+        },
+      ]
+    );
+    assert_eq!(
+      merge.tree.root().paths("/", NodeFilter::default()),
+      [
+        "/".to_owned(),
+        "/ours".to_owned(),
+        format!("/ours/{CONFLICT_MARKER}name"),
+        format!("/ours/{CONFLICT_MARKER}name/ours: ours"),
+        format!("/ours/{CONFLICT_MARKER}name/theirs: theirs"),
+        "/ours/d".to_owned(),
+        "/b".to_owned(),
+        format!("/b/{CONFLICT_MARKER}parent"),
+        format!(r"/b/{CONFLICT_MARKER}parent/ours: \/"),
+        format!(r"/b/{CONFLICT_MARKER}parent/theirs: \/theirs\/c"),
+        "/b/c".to_owned(),
+      ]
+    );
+  }
+
+  #[test]
+  fn node_ids() {
+    let (tree, _) = base();
+    let mut encoded = serde_json::to_value(&tree).unwrap();
+    assert!(has_node_ids(&encoded));
+
+    encoded["children"][0]["children"][1]
+      .as_object_mut()
+      .unwrap()
+      .remove("id");
+    assert!(!has_node_ids(&encoded));
+
+    let v1 = include_str!("../fixtures/v1/tree.json");
+    assert!(!has_node_ids(&serde_json::from_str(v1).unwrap()));
+  }
+}
@@ -1,13 +1,29 @@
 //! Node operations
 
-use crate::encoding::{self, TreeType};
+pub mod query;
+
+use crate::{
+  data_file::{DataFileStore, DataFileStoreError},
+  encoding::{self, TreeType},
+  history::{History, Operation},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use query::Query;
 use serde::{Deserialize, Serialize};
 use std::{
+  borrow::Cow,
+  cmp::Ordering,
+  collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+  convert::Infallible,
+  fmt,
   io::{self, Write},
-  path::PathBuf,
+  mem,
+  path::{Path, PathBuf},
+  str::FromStr,
   sync::{Arc, RwLock, RwLockReadGuard, Weak},
 };
 use thiserror::Error;
+use uuid::Uuid;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(from = "encoding::Tree", into = "encoding::Tree")]
@@ -15,6 +31,7 @@ pub struct Tree {
   version: encoding::Version,
   ty: TreeType,
   node: Node,
+  history: Arc<RwLock<History>>,
 }
 
 impl From<encoding::Tree> for Tree {
@@ -35,6 +52,7 @@ impl Tree {
       version: encoding::Version::current(),
       ty: TreeType::Root,
       node: Node::new(name, icon),
+      history: Arc::default(),
     }
   }
 
@@ -43,6 +61,7 @@ impl Tree {
       version: tree.version,
       ty: tree.ty,
       node: Node::from_encoding(tree.node),
+      history: Arc::default(),
     }
   }
 
@@ -59,6 +78,33 @@ impl Tree {
     self.node.clone()
   }
 
+  /// Apply an [`Operation`] to the tree, recording it so that it can be undone.
+  pub fn apply(&self, operation: Operation) -> Result<(), NodeError> {
+    self.history.write().unwrap().apply(operation)
+  }
+
+  /// Undo the last applied operation.
+  ///
+  /// Returns the node affected by the undo, if any; `None` means there was nothing to undo.
+  pub fn undo(&self) -> Result<Option<Node>, NodeError> {
+    self.history.write().unwrap().undo()
+  }
+
+  /// Redo the last undone operation.
+  ///
+  /// Returns the node affected by the redo, if any; `None` means there was nothing to redo.
+  pub fn redo(&self) -> Result<Option<Node>, NodeError> {
+    self.history.write().unwrap().redo()
+  }
+
+  /// Start a [`Transaction`] on the tree.
+  pub fn transaction(&self) -> Transaction<'_> {
+    Transaction {
+      tree: self,
+      inverses: Vec::new(),
+    }
+  }
+
   /// Get a [`Node`] by line number.
   ///
   /// 0-indexed.
@@ -67,15 +113,153 @@ impl Tree {
     node
   }
 
+  /// Get a [`Node`] by its [`NodeId`].
+  ///
+  /// Clones share the ID of the node they are cloned from, so only the first occurrence, in depth-first order, is
+  /// returned; see [`Tree::get_nodes_by_id`] and [`Tree::select_node_by_id`] to get the other ones.
+  pub fn get_node_by_id(&self, id: NodeId) -> Option<Node> {
+    self.node.get_node_by_id(id)
+  }
+
+  /// Get all the occurrences of the node with the given [`NodeId`], in depth-first order.
+  pub fn get_nodes_by_id(&self, id: NodeId) -> Vec<Node> {
+    self
+      .node
+      .subtree()
+      .into_iter()
+      .filter(|node| node.id() == id)
+      .collect()
+  }
+
+  /// Select a [`Node`] by ID, e.g. `<ID>`, or `<ID>[N]` to select the N-th occurrence of a cloned node.
+  ///
+  /// If the node is cloned and no occurrence is selected, [`NodeError::AmbiguousId`] is returned.
+  pub fn select_node_by_id(&self, selection: &str) -> Result<Option<Node>, NodeError> {
+    let selection = selection.trim();
+    let (id, index) = match selection
+      .strip_suffix(']')
+      .and_then(|selection| selection.split_once('['))
+    {
+      Some((id, index)) => {
+        let index: usize = index
+          .parse()
+          .map_err(|_| NodeError::InvalidId(selection.to_owned()))?;
+        (id.parse()?, Some(index))
+      }
+      None => (selection.parse()?, None),
+    };
+
+    let mut occurrences = self.get_nodes_by_id(id);
+    match index {
+      Some(index) => Ok(
+        index
+          .checked_sub(1)
+          .filter(|&i| i < occurrences.len())
+          .map(|i| occurrences.swap_remove(i)),
+      ),
+      None if occurrences.len() > 1 => Err(NodeError::AmbiguousId(id, occurrences.len())),
+      None => Ok(occurrences.pop()),
+    }
+  }
+
+  /// All the nodes referencing the node with the given [`NodeId`] in their attachments.
+  pub fn backlinks(&self, id: NodeId) -> Vec<Node> {
+    let reference = NodeData::Node(id);
+
+    self
+      .node
+      .subtree()
+      .into_iter()
+      .filter(|node| {
+        node
+          .content()
+          .read()
+          .unwrap()
+          .attachments
+          .contains(&reference)
+      })
+      .collect()
+  }
+
   /// Get a [`Node`] by path, e.g. `/root/a/b/c/d`.
-  pub fn get_node_by_path<'a>(
+  ///
+  /// If several siblings match a segment that has no index, [`NodeError::AmbiguousPath`] is returned.
+  pub fn get_node_by_path(
     &self,
-    path: impl IntoIterator<Item = &'a str>,
+    path: impl IntoIterator<Item = impl Into<PathSegment>>,
     auto_create_nodes: bool,
-  ) -> Option<Node> {
+  ) -> Result<Option<Node>, NodeError> {
     self
       .node
-      .get_node_by_path(path.into_iter(), auto_create_nodes)
+      .get_node_by_path(path.into_iter().map(Into::into), auto_create_nodes)
+  }
+}
+
+/// Operations applied to a [`Tree`] as a whole.
+///
+/// Operations are applied as soon as they are added to the transaction, so that the next ones see their effects. If
+/// one of them fails, or if the transaction is dropped without being committed, all the operations already applied are
+/// rolled back, leaving the tree as it was before the transaction started.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+  tree: &'a Tree,
+
+  /// Inverses of the applied operations, the most recent last.
+  inverses: Vec<Operation>,
+}
+
+impl Transaction<'_> {
+  /// Apply an operation.
+  ///
+  /// If the operation fails, the whole transaction is rolled back.
+  pub fn apply(&mut self, operation: Operation) -> Result<(), NodeError> {
+    match operation.apply() {
+      Ok(inverse) => {
+        self.inverses.push(inverse);
+        Ok(())
+      }
+
+      Err(err) => {
+        self.rollback_in_place()?;
+        Err(err)
+      }
+    }
+  }
+
+  /// Commit the transaction, recording it as a single operation in the history of the tree.
+  pub fn commit(mut self) {
+    let mut inverses = mem::take(&mut self.inverses);
+
+    if !inverses.is_empty() {
+      inverses.reverse();
+      self
+        .tree
+        .history
+        .write()
+        .unwrap()
+        .record(Operation::Group(inverses));
+    }
+  }
+
+  /// Roll back all the operations applied so far.
+  pub fn rollback(mut self) -> Result<(), NodeError> {
+    self.rollback_in_place()
+  }
+
+  fn rollback_in_place(&mut self) -> Result<(), NodeError> {
+    while let Some(inverse) = self.inverses.pop() {
+      inverse.apply()?;
+    }
+
+    Ok(())
+  }
+}
+
+impl Drop for Transaction<'_> {
+  fn drop(&mut self) {
+    if let Err(err) = self.rollback_in_place() {
+      log::error!("cannot roll back transaction: {err}");
+    }
   }
 }
 
@@ -121,27 +305,201 @@ impl From<Node> for encoding::Node {
 
 impl Node {
   pub fn new(name: impl AsRef<str>, icon: impl AsRef<str>) -> Self {
-    Self::new_raw(name.as_ref(), icon.as_ref(), false, None)
+    Self::new_raw(NodeId::new(), name.as_ref(), icon.as_ref(), false, None)
   }
 
-  fn new_raw(name: &str, icon: &str, is_expanded: bool, parent: Option<WeakNode>) -> Self {
+  fn new_raw(
+    id: NodeId,
+    name: &str,
+    icon: &str,
+    is_expanded: bool,
+    parent: Option<WeakNode>,
+  ) -> Self {
     let name = name.trim().to_owned();
 
     // we only trim left because sometimes, the right space is meaningful for some icons
     let icon = icon.trim_start().to_owned();
-
-    Self {
+    let now = Utc::now();
+
+    let content = NodeContent {
+      id,
+      name,
+      icon,
+      created_at: Some(now),
+      modified_at: Some(now),
+      tags: BTreeSet::new(),
+      properties: BTreeMap::new(),
+      status: None,
+      body: None,
+      due: None,
+      scheduled: None,
+      attachments: Vec::new(),
+      occurrences: Vec::new(),
+    };
+
+    Self::with_content(Arc::new(RwLock::new(content)), is_expanded, parent)
+  }
+
+  /// Create a new occurrence of a node with the given content.
+  fn with_content(
+    content: Arc<RwLock<NodeContent>>,
+    is_expanded: bool,
+    parent: Option<WeakNode>,
+  ) -> Self {
+    let node = Self {
       inner: Arc::new(RwLock::new(NodeInner {
-        name,
-        icon,
+        content: content.clone(),
         is_expanded,
         parent,
         prev: None,
         next: None,
-        data: None,
         children: Vec::new(),
       })),
+    };
+
+    let mut content = content.write().unwrap();
+    content
+      .occurrences
+      .retain(|occurrence| occurrence.inner.strong_count() > 0);
+    content.occurrences.push(node.downgrade());
+    drop(content);
+
+    node
+  }
+
+  /// Content of the node, shared with its clones.
+  fn content(&self) -> Arc<RwLock<NodeContent>> {
+    self.inner.read().unwrap().content.clone()
+  }
+
+  /// Create a clone of this node.
+  ///
+  /// A clone is another occurrence of the same logical node, meant to be inserted somewhere else in the tree: it shares
+  /// everything with the node (ID, name, icon, tags, data, etc.), so that a change made through one occurrence is
+  /// visible in all of them. Each occurrence has its own position in the tree, its own children and its own expanded
+  /// state, though: the clone starts without children, and children added to an occurrence don’t appear in the other
+  /// ones.
+  ///
+  /// Because occurrences share their ID, lookups by ID return the first occurrence; see [`Tree::select_node_by_id`].
+  ///
+  /// This is different from [`Clone::clone`], which only creates a new handle to the very same occurrence.
+  pub fn new_clone(&self) -> Node {
+    Self::with_content(self.content(), false, None)
+  }
+
+  /// Create a deep copy of the subtree rooted at this node.
+  ///
+  /// Unlike [`Node::new_clone`], the copy and all of its descendants are new nodes, with their own IDs and content, so
+  /// that modifying them leaves the original nodes untouched. Nodes cloned within the subtree are still clones of each
+  /// other in the copy. The copy has no parent and attached data files are shared with the original nodes; see
+  /// [`Node::deep_copy_with_data_files`] to duplicate them as well.
+  pub fn deep_copy(&self) -> Node {
+    let copy = self.deep_copy_rec(None, &mut HashMap::new(), &mut |_, path| {
+      Ok::<_, Infallible>(path.to_owned())
+    });
+
+    match copy {
+      Ok(copy) => copy,
+      Err(never) => match never {},
+    }
+  }
+
+  /// Create a deep copy of the subtree rooted at this node, duplicating attached data files in `store`.
+  ///
+  /// See [`Node::deep_copy`].
+  pub fn deep_copy_with_data_files(
+    &self,
+    store: &DataFileStore,
+  ) -> Result<Node, DataFileStoreError> {
+    self.deep_copy_rec(None, &mut HashMap::new(), &mut |name, path| {
+      store.copy_data_file(name, path)
+    })
+  }
+
+  fn deep_copy_rec<E>(
+    &self,
+    parent: Option<WeakNode>,
+    contents: &mut HashMap<NodeId, Arc<RwLock<NodeContent>>>,
+    copy_file: &mut impl FnMut(&str, &Path) -> Result<PathBuf, E>,
+  ) -> Result<Node, E> {
+    let content = self.content();
+    let content = content.read().unwrap();
+
+    let copied_content = match contents.get(&content.id) {
+      Some(copied_content) => copied_content.clone(),
+      None => {
+        let attachments = content
+          .attachments
+          .iter()
+          .map(|data| match data {
+            NodeData::File(path) => copy_file(&content.name, path).map(NodeData::File),
+            _ => Ok(data.clone()),
+          })
+          .collect::<Result<_, _>>()?;
+        let now = Utc::now();
+        let copied_content = Arc::new(RwLock::new(NodeContent {
+          id: NodeId::new(),
+          created_at: Some(now),
+          modified_at: Some(now),
+          attachments,
+          occurrences: Vec::new(),
+          ..content.clone()
+        }));
+
+        contents.insert(content.id, copied_content.clone());
+        copied_content
+      }
+    };
+    drop(content);
+
+    let copy = Self::with_content(copied_content, self.is_expanded(), parent);
+    let children = self
+      .children()
+      .into_iter()
+      .map(|child| child.deep_copy_rec(Some(copy.downgrade()), contents, copy_file))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    // set prev / next in children
+    for (a, b) in children.iter().zip(children.iter().skip(1)) {
+      a.inner.write().unwrap().next = Some(b.clone());
+      b.inner.write().unwrap().prev = Some(a.clone());
     }
+
+    copy.inner.write().unwrap().children = children;
+    Ok(copy)
+  }
+
+  /// Check whether both nodes are occurrences of the same logical node.
+  pub fn is_clone_of(&self, other: &Node) -> bool {
+    Arc::ptr_eq(&self.content(), &other.content())
+  }
+
+  /// Other occurrences of this node that are currently inserted in a tree.
+  pub fn clones(&self) -> Vec<Node> {
+    self
+      .content()
+      .read()
+      .unwrap()
+      .occurrences
+      .iter()
+      .filter_map(WeakNode::upgrade)
+      .filter(|node| node != self && node.parent().is_ok())
+      .collect()
+  }
+
+  /// Check whether the node has other occurrences inserted in a tree.
+  pub fn is_cloned(&self) -> bool {
+    !self.clones().is_empty()
+  }
+
+  /// Create a copy of this node with the same ID and content, but without position, children nor clones.
+  pub(crate) fn detached_copy(&self) -> Node {
+    let content = NodeContent {
+      occurrences: Vec::new(),
+      ..self.content().read().unwrap().clone()
+    };
+
+    Self::with_content(Arc::new(RwLock::new(content)), self.is_expanded(), None)
   }
 
   fn downgrade(&self) -> WeakNode {
@@ -151,25 +509,28 @@ impl Node {
   }
 
   pub fn from_encoding(node: encoding::Node) -> Self {
-    Self::from_encoding_rec(None, node)
-  }
-
-  fn from_encoding_rec(parent: Option<WeakNode>, mut node: encoding::Node) -> Self {
-    let current = Self::new_raw(
-      &node
-        .contents
-        .pop()
-        .map(|text| text.text)
-        .unwrap_or_default(),
-      &node.icon,
-      node.is_expanded,
-      parent,
-    );
+    // contents are created upfront, so that clones can share the content of nodes appearing anywhere in the tree
+    let mut contents = HashMap::new();
+    NodeContent::collect_encoding(&node, &mut contents);
+
+    Self::from_encoding_rec(None, node, &contents)
+  }
+
+  fn from_encoding_rec(
+    parent: Option<WeakNode>,
+    node: encoding::Node,
+    contents: &HashMap<NodeId, Arc<RwLock<NodeContent>>>,
+  ) -> Self {
+    let content = contents.get(&node.id).cloned().unwrap_or_else(|| {
+      log::warn!("clone of unknown node {}", node.id);
+      Arc::new(RwLock::new(NodeContent::from_encoding(&node)))
+    });
+    let current = Self::with_content(content, node.is_expanded, parent);
 
     let children: Vec<_> = node
       .children
       .into_iter()
-      .map(|node| Self::from_encoding_rec(Some(current.downgrade()), node))
+      .map(|node| Self::from_encoding_rec(Some(current.downgrade()), node, contents))
       .collect();
 
     // set prev / next in children
@@ -178,52 +539,50 @@ impl Node {
       b.inner.write().unwrap().prev = Some(a.clone());
     }
 
-    let data = node
-      .data
-      .map(NodeData::file)
-      .or_else(move || node.url.map(NodeData::link));
-
-    {
-      let mut inner = current.inner.write().unwrap();
-      inner.children = children;
-      inner.data = data;
-    }
-
+    current.inner.write().unwrap().children = children;
     current
   }
 
   pub fn into_encoding(&self) -> encoding::Node {
-    let node = self.inner.read().unwrap();
-    let data;
-    let url;
+    self.encode_rec(&mut HashSet::new())
+  }
 
-    match node.data {
-      Some(NodeData::File(ref path)) => {
-        data = Some(path.clone());
-        url = None;
-      }
+  /// Encode the node; only the first occurrence of a cloned node is encoded with its content, the other ones are
+  /// encoded as clones.
+  fn encode_rec(&self, encoded: &mut HashSet<NodeId>) -> encoding::Node {
+    let node = self.inner.read().unwrap();
+    let content = node.content.read().unwrap();
+    let is_clone = !encoded.insert(content.id);
 
-      Some(NodeData::Link(ref link)) => {
-        url = Some(link.clone());
-        data = None;
+    let mut encoded_node = if is_clone {
+      encoding::Node {
+        is_clone,
+        ..encoding::Node::new(content.id, String::new())
       }
-
-      None => {
-        data = None;
-        url = None;
+    } else {
+      encoding::Node {
+        icon: content.icon.clone(),
+        created_at: content.created_at,
+        modified_at: content.modified_at,
+        tags: content.tags.clone(),
+        properties: content.properties.clone(),
+        status: content.status,
+        body: content.body.clone(),
+        due: content.due,
+        scheduled: content.scheduled,
+        attachments: content.attachments.clone(),
+        ..encoding::Node::new(content.id, content.name.clone())
       }
-    }
+    };
+    drop(content);
 
-    encoding::Node {
-      icon: node.icon.clone(),
-      is_expanded: node.is_expanded,
-      contents: vec![encoding::Text {
-        text: node.name.clone(),
-      }],
-      data,
-      url,
-      children: node.children.iter().map(Self::into_encoding).collect(),
-    }
+    encoded_node.is_expanded = node.is_expanded;
+    encoded_node.children = node
+      .children
+      .iter()
+      .map(|child| child.encode_rec(encoded))
+      .collect();
+    encoded_node
   }
 
   fn get_node_by_line(&self, mut line: usize) -> (usize, Option<Self>) {
@@ -252,38 +611,61 @@ impl Node {
     (line, None)
   }
 
-  fn get_node_by_path<'a>(
+  fn get_node_by_id(&self, id: NodeId) -> Option<Self> {
+    if self.id() == id {
+      return Some(self.clone());
+    }
+
+    let node = self.inner.read().unwrap();
+
+    node
+      .children
+      .iter()
+      .find_map(|child| child.get_node_by_id(id))
+  }
+
+  fn get_node_by_path(
     &self,
-    mut path: impl Iterator<Item = &'a str>,
+    mut path: impl Iterator<Item = PathSegment>,
     auto_create_nodes: bool,
-  ) -> Option<Self> {
-    let node = self.inner.read().unwrap();
+  ) -> Result<Option<Self>, NodeError> {
+    let segment = match path.next() {
+      None => return Ok(Some(self.clone())),
+      Some(segment) => segment,
+    };
 
-    match path.next() {
-      None => Some(self.clone()),
+    // find the node in the children list, and if it doesn’t exist, it means the node we are looking for doesn’t exist
+    let node = self.inner.read().unwrap();
+    let mut candidates = node
+      .children
+      .iter()
+      .filter(|child| child.name() == segment.name);
+    let child = match segment.index {
+      Some(index) => index.checked_sub(1).and_then(|n| candidates.nth(n)),
+      None => {
+        let candidates: Vec<_> = candidates.collect();
 
-      Some(node_name) => {
-        // find the node in the children list, and if it doesn’t exist, it means the node we are looking for doesn’t exist;
-        // abort early if we don’t need to create the node
-        match node
-          .children
-          .iter()
-          .find(|node| node.inner.read().unwrap().name == node_name)
-        {
-          Some(child) => child.get_node_by_path(path, auto_create_nodes),
-          None => {
-            drop(node);
-
-            if auto_create_nodes {
-              let child = Node::new(node_name, "");
-              self.insert_bottom(child.clone());
-              child.get_node_by_path(path, auto_create_nodes)
-            } else {
-              None
-            }
-          }
+        if candidates.len() > 1 {
+          return Err(NodeError::AmbiguousPath(segment.name, candidates.len()));
         }
+
+        candidates.first().copied()
+      }
+    }
+    .cloned();
+    drop(node);
+
+    match child {
+      Some(child) => child.get_node_by_path(path, auto_create_nodes),
+
+      // abort early if we don’t need to create the node; we never create nodes selected by index
+      None if auto_create_nodes && segment.index.is_none() => {
+        let child = Node::new(&segment.name, "");
+        self.insert_bottom(child.clone());
+        child.get_node_by_path(path, auto_create_nodes)
       }
+
+      None => Ok(None),
     }
   }
 
@@ -300,12 +682,12 @@ impl Node {
     Err(NodeError::NotContainedInParent)
   }
 
-  #[cfg(test)]
-  fn get_index_from_parent(&self) -> Result<usize, NodeError> {
+  /// Get the index of a [`Node`] in its parent.
+  pub(crate) fn get_index_from_parent(&self) -> Result<usize, NodeError> {
     self.parent().and_then(|parent| self.get_index(&parent))
   }
 
-  pub fn children(&self) -> Children {
+  pub fn children(&self) -> Children<'_> {
     Children {
       borrow: self.inner.read().unwrap(),
     }
@@ -315,8 +697,53 @@ impl Node {
     !self.inner.read().unwrap().children.is_empty()
   }
 
+  /// Stable identifier of the node.
+  pub fn id(&self) -> NodeId {
+    self.content().read().unwrap().id
+  }
+
+  /// Absolute path of the node in its tree, e.g. `/a/b/c`.
+  ///
+  /// The root node’s path is `/`. See [`PathSegment`] for how segments are written.
+  pub fn path(&self) -> String {
+    let mut segments = Vec::new();
+    let mut node = self.clone();
+
+    while let Ok(parent) = node.parent() {
+      segments.push(node.path_segment(&parent).to_string());
+      node = parent;
+    }
+
+    segments.reverse();
+    format!("/{}", segments.join("/"))
+  }
+
+  /// Path segment selecting this node in its parent.
+  ///
+  /// The segment has an index only if the node has siblings with the same name.
+  fn path_segment(&self, parent: &Node) -> PathSegment {
+    let name = self.name();
+    let mut index = 0;
+    let mut count = 0;
+
+    for sibling in &parent.inner.read().unwrap().children {
+      if sibling.name() == name {
+        count += 1;
+
+        if sibling == self {
+          index = count;
+        }
+      }
+    }
+
+    PathSegment {
+      name,
+      index: (count > 1).then_some(index),
+    }
+  }
+
   pub fn name(&self) -> String {
-    self.inner.read().unwrap().name.to_owned()
+    self.content().read().unwrap().name.to_owned()
   }
 
   pub fn set_name(&self, name: impl AsRef<str>) -> Result<(), NodeError> {
@@ -326,12 +753,14 @@ impl Node {
       return Err(NodeError::EmptyName);
     }
 
-    self.inner.write().unwrap().name = name;
+    self.content().write().unwrap().name = name;
+    self.touch();
     Ok(())
   }
 
   pub fn icon(&self) -> String {
-    let icon = &self.inner.read().unwrap().icon;
+    let content = self.content();
+    let icon = &content.read().unwrap().icon;
 
     if icon.is_empty() {
       String::new()
@@ -340,132 +769,389 @@ impl Node {
     }
   }
 
+  /// Icon as stored, without the padding added by [`Node::icon`]; trailing spaces are part of some icons.
+  pub(crate) fn raw_icon(&self) -> String {
+    self.content().read().unwrap().icon.clone()
+  }
+
   pub fn set_icon(&self, icon: impl AsRef<str>) {
     let icon = icon.as_ref().trim_start().to_owned();
-    self.inner.write().unwrap().icon = icon;
+    self.content().write().unwrap().icon = icon;
+    self.touch();
   }
 
-  pub fn data(&self) -> Option<NodeData> {
-    self.inner.read().unwrap().data.clone()
+  /// Files, links and node references attached to the node, in the order they were attached.
+  pub fn attachments(&self) -> Vec<NodeData> {
+    self.content().read().unwrap().attachments.clone()
   }
 
-  pub fn set_data(&self, data: NodeData) -> Result<(), NodeError> {
-    let current = self.inner.read().unwrap();
-
-    match (current.data.as_ref(), &data) {
-      // if nothing is set, set it
-      (None, NodeData::Link(link)) => {
-        if link.is_empty() {
-          return Err(NodeError::NoData);
-        }
+  /// Replace all the attachments of the node, without any validation.
+  pub(crate) fn set_attachments(&self, attachments: Vec<NodeData>) {
+    self.content().write().unwrap().attachments = attachments;
+    self.touch();
+  }
 
-        drop(current);
-        self.inner.write().unwrap().data = Some(data);
-      }
+  pub fn has_attachments(&self) -> bool {
+    !self.content().read().unwrap().attachments.is_empty()
+  }
 
-      (None, NodeData::File(path)) => {
-        if path.as_os_str().is_empty() {
-          return Err(NodeError::NoData);
-        }
+  /// Attach a file, a link or a reference to another node to the node.
+  ///
+  /// The same data cannot be attached twice to the same node, and a node cannot reference itself.
+  pub fn add_attachment(&self, data: NodeData) -> Result<(), NodeError> {
+    let is_empty = match data {
+      NodeData::File(ref path) => path.as_os_str().is_empty(),
+      NodeData::Link(ref link) => link.trim().is_empty(),
+      NodeData::Node(id) if id == self.id() => return Err(NodeError::SelfReference),
+      NodeData::Node(_) => false,
+    };
+
+    if is_empty {
+      return Err(NodeError::NoData);
+    }
 
-        drop(current);
-        self.inner.write().unwrap().data = Some(data);
-      }
+    {
+      let content = self.content();
+      let mut node = content.write().unwrap();
 
-      (Some(NodeData::Link(_)), NodeData::Link(_)) => {
-        drop(current);
-        self.inner.write().unwrap().data = Some(data)
+      if node.attachments.contains(&data) {
+        return Err(NodeError::AttachmentAlreadyExists);
       }
 
-      (Some(NodeData::File(_)), NodeData::File(_)) => return Err(NodeError::FileDataAlreadyExists),
-
-      // otherwise it’s a data type mismatch
-      _ => return Err(NodeError::MismatchDataType),
+      node.attachments.push(data);
     }
 
+    self.touch();
     Ok(())
   }
 
-  pub fn is_expanded(&self) -> bool {
-    self.inner.read().unwrap().is_expanded
-  }
+  /// Detach the attachment at the given index, returning it.
+  pub fn remove_attachment(&self, index: usize) -> Result<NodeData, NodeError> {
+    let data = {
+      let content = self.content();
+      let mut node = content.write().unwrap();
 
-  pub fn set_expanded(&self, is_expanded: bool) {
-    self.inner.write().unwrap().is_expanded = is_expanded;
+      if index >= node.attachments.len() {
+        return Err(NodeError::NoSuchAttachment(index));
+      }
+
+      node.attachments.remove(index)
+    };
+
+    self.touch();
+    Ok(data)
   }
 
-  pub fn parent(&self) -> Result<Node, NodeError> {
-    self
-      .inner
-      .read()
-      .unwrap()
-      .parent
-      .as_ref()
-      .and_then(WeakNode::upgrade)
-      .ok_or(NodeError::NoParent)
+  /// Date and time at which the node was created.
+  ///
+  /// Nodes created before timestamps were recorded don’t have this information.
+  pub fn created_at(&self) -> Option<DateTime<Utc>> {
+    self.content().read().unwrap().created_at
   }
 
-  pub fn prev(&self) -> Option<Node> {
-    self.inner.read().unwrap().prev.clone()
+  /// Date and time at which the node was last modified.
+  ///
+  /// A node is modified when its name, icon or data changes, when it is inserted or moved, and when its children list
+  /// changes.
+  pub fn modified_at(&self) -> Option<DateTime<Utc>> {
+    self.content().read().unwrap().modified_at
   }
 
-  pub fn next(&self) -> Option<Node> {
-    self.inner.read().unwrap().next.clone()
+  /// Tags of the node, sorted.
+  pub fn tags(&self) -> BTreeSet<String> {
+    self.content().read().unwrap().tags.clone()
   }
 
-  pub fn insert_top(&self, node: Node) {
-    node.inner.write().unwrap().parent = Some(self.downgrade());
+  /// Check whether the node has the given tag.
+  ///
+  /// The tag can be passed with or without its leading `#`.
+  pub fn has_tag(&self, tag: impl AsRef<str>) -> bool {
+    let tag = tag.as_ref().trim();
+    let tag = tag.strip_prefix('#').unwrap_or(tag);
+    self.content().read().unwrap().tags.contains(tag)
+  }
 
-    let mut children = self.inner.write().unwrap();
-    if let Some(first) = children.children.first() {
-      first.inner.write().unwrap().prev = Some(node.clone());
-      node.inner.write().unwrap().next = Some(first.clone());
+  /// Add a tag to the node.
+  ///
+  /// The tag can be passed with or without its leading `#`, which is not stored. Return `false` if the node already
+  /// had the tag.
+  pub fn add_tag(&self, tag: impl AsRef<str>) -> Result<bool, NodeError> {
+    let tag = Self::normalize_tag(tag.as_ref())?;
+    let added = self.content().write().unwrap().tags.insert(tag);
+
+    if added {
+      self.touch();
     }
 
-    children.children.insert(0, node);
+    Ok(added)
   }
 
-  pub fn insert_bottom(&self, node: Node) {
-    node.inner.write().unwrap().parent = Some(self.downgrade());
-
-    let mut children = self.inner.write().unwrap();
-    if let Some(last) = children.children.last() {
-      last.inner.write().unwrap().next = Some(node.clone());
-      node.inner.write().unwrap().prev = Some(last.clone());
+  /// Remove a tag from the node.
+  ///
+  /// Return `false` if the node didn’t have the tag.
+  pub fn remove_tag(&self, tag: impl AsRef<str>) -> bool {
+    let tag = tag.as_ref().trim();
+    let tag = tag.strip_prefix('#').unwrap_or(tag);
+    let removed = self.content().write().unwrap().tags.remove(tag);
+
+    if removed {
+      self.touch();
     }
 
-    children.children.push(node);
+    removed
   }
 
-  pub fn insert_before(&self, node: Node) -> Result<(), NodeError> {
-    let parent = self.parent()?;
+  /// Properties of the node, sorted by key.
+  pub fn properties(&self) -> BTreeMap<String, String> {
+    self.content().read().unwrap().properties.clone()
+  }
 
-    // set parent link, prev / next between selected node and node to insert
-    {
-      let mut inner = node.inner.write().unwrap();
-      inner.parent = Some(parent.downgrade());
-      inner.next = Some(self.clone());
-      self.inner.write().unwrap().prev = Some(node.clone());
-    }
+  /// Get the value of a property.
+  pub fn property(&self, key: impl AsRef<str>) -> Option<String> {
+    self
+      .content()
+      .read()
+      .unwrap()
+      .properties
+      .get(key.as_ref().trim())
+      .cloned()
+  }
 
-    let i = self.get_index(&parent)?;
-    let mut children = parent.inner.write().unwrap();
+  /// Set the value of a property, returning its previous value, if any.
+  ///
+  /// Keys are trimmed and cannot be empty nor contain `=`.
+  pub fn set_property(
+    &self,
+    key: impl AsRef<str>,
+    value: impl Into<String>,
+  ) -> Result<Option<String>, NodeError> {
+    let key = key.as_ref().trim();
 
-    // if there is a node in the children before the one we add, we set its next and set our prev
-    if i > 0 {
-      if let Some(prev) = children.children.get(i - 1) {
-        prev.inner.write().unwrap().next = Some(node.clone());
-        node.inner.write().unwrap().prev = Some(prev.clone());
-      }
+    if key.is_empty() || key.contains('=') {
+      return Err(NodeError::InvalidPropertyKey(key.to_owned()));
     }
 
-    children.children.insert(i, node);
-
-    Ok(())
+    let previous = self
+      .content()
+      .write()
+      .unwrap()
+      .properties
+      .insert(key.to_owned(), value.into());
+    self.touch();
+    Ok(previous)
   }
 
-  pub fn insert_after(&self, node: Node) -> Result<(), NodeError> {
-    let parent = self.parent()?;
+  /// Remove a property, returning its value, if any.
+  pub fn unset_property(&self, key: impl AsRef<str>) -> Option<String> {
+    let previous = self
+      .content()
+      .write()
+      .unwrap()
+      .properties
+      .remove(key.as_ref().trim());
+
+    if previous.is_some() {
+      self.touch();
+    }
+
+    previous
+  }
+
+  /// Task status of the node, if it is a task.
+  pub fn status(&self) -> Option<TaskStatus> {
+    self.content().read().unwrap().status
+  }
+
+  /// Set the task status of the node; [`None`] turns it back into a regular node.
+  pub fn set_status(&self, status: Option<TaskStatus>) {
+    self.content().write().unwrap().status = status;
+    self.touch();
+  }
+
+  /// Cycle the task status of the node, returning the new status.
+  ///
+  /// See [`TaskStatus::cycle`] for the order in which statuses are visited.
+  pub fn cycle_status(&self) -> Option<TaskStatus> {
+    let status = TaskStatus::cycle(self.status());
+    self.set_status(status);
+    status
+  }
+
+  /// Task progress of the descendants of this node.
+  ///
+  /// Every task in the subtree (not including this node) is accounted for, except cancelled ones. Cloned tasks are
+  /// accounted for only once.
+  ///
+  /// This walks the whole subtree; see [`SubtreeProgress`] to get the progress of many nodes at once.
+  pub fn progress(&self) -> Progress {
+    let mut progress = Progress::default();
+    let mut seen = HashSet::new();
+
+    for node in self.subtree().into_iter().skip(1) {
+      if seen.insert(node.id()) {
+        progress.add(node.status());
+      }
+    }
+
+    progress
+  }
+
+  /// Body text of the node, if any.
+  ///
+  /// The body is free-form, possibly multi-line, text stored along with the node.
+  pub fn body(&self) -> Option<String> {
+    self.content().read().unwrap().body.clone()
+  }
+
+  /// Set or clear the body text of the node.
+  ///
+  /// Trailing whitespace is removed; a blank body clears it.
+  pub fn set_body(&self, body: Option<impl AsRef<str>>) {
+    let body = body
+      .map(|body| body.as_ref().trim_end().to_owned())
+      .filter(|body| !body.trim().is_empty());
+    self.content().write().unwrap().body = body;
+    self.touch();
+  }
+
+  /// Date at which the node is due, if any.
+  pub fn due(&self) -> Option<NaiveDate> {
+    self.content().read().unwrap().due
+  }
+
+  /// Set or clear the due date of the node.
+  pub fn set_due(&self, due: Option<NaiveDate>) {
+    self.content().write().unwrap().due = due;
+    self.touch();
+  }
+
+  /// Date at which the node is scheduled, if any.
+  pub fn scheduled(&self) -> Option<NaiveDate> {
+    self.content().read().unwrap().scheduled
+  }
+
+  /// Set or clear the scheduled date of the node.
+  pub fn set_scheduled(&self, scheduled: Option<NaiveDate>) {
+    self.content().write().unwrap().scheduled = scheduled;
+    self.touch();
+  }
+
+  fn normalize_tag(tag: &str) -> Result<String, NodeError> {
+    let tag = tag.trim();
+    let tag = tag.strip_prefix('#').unwrap_or(tag);
+
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+      return Err(NodeError::InvalidTag(tag.to_owned()));
+    }
+
+    Ok(tag.to_owned())
+  }
+
+  /// Mark the node as modified now.
+  fn touch(&self) {
+    self.content().write().unwrap().modified_at = Some(Utc::now());
+  }
+
+  pub fn is_expanded(&self) -> bool {
+    self.inner.read().unwrap().is_expanded
+  }
+
+  pub fn set_expanded(&self, is_expanded: bool) {
+    self.inner.write().unwrap().is_expanded = is_expanded;
+  }
+
+  pub fn parent(&self) -> Result<Node, NodeError> {
+    self
+      .inner
+      .read()
+      .unwrap()
+      .parent
+      .as_ref()
+      .and_then(WeakNode::upgrade)
+      .ok_or(NodeError::NoParent)
+  }
+
+  pub fn prev(&self) -> Option<Node> {
+    self.inner.read().unwrap().prev.clone()
+  }
+
+  pub fn next(&self) -> Option<Node> {
+    self.inner.read().unwrap().next.clone()
+  }
+
+  pub fn insert_top(&self, node: Node) {
+    node.inner.write().unwrap().parent = Some(self.downgrade());
+    node.touch();
+    self.touch();
+
+    let mut children = self.inner.write().unwrap();
+    if let Some(first) = children.children.first() {
+      first.inner.write().unwrap().prev = Some(node.clone());
+      node.inner.write().unwrap().next = Some(first.clone());
+    }
+
+    children.children.insert(0, node);
+  }
+
+  pub fn insert_bottom(&self, node: Node) {
+    node.inner.write().unwrap().parent = Some(self.downgrade());
+    node.touch();
+    self.touch();
+
+    let mut children = self.inner.write().unwrap();
+    if let Some(last) = children.children.last() {
+      last.inner.write().unwrap().next = Some(node.clone());
+      node.inner.write().unwrap().prev = Some(last.clone());
+    }
+
+    children.children.push(node);
+  }
+
+  /// Insert a node as the child at the given index, or at the bottom if the index is past the last child.
+  pub(crate) fn insert_at(&self, index: usize, node: Node) -> Result<(), NodeError> {
+    let sibling = self.inner.read().unwrap().children.get(index).cloned();
+
+    match sibling {
+      Some(sibling) => sibling.insert_before(node),
+      None => {
+        self.insert_bottom(node);
+        Ok(())
+      }
+    }
+  }
+
+  pub fn insert_before(&self, node: Node) -> Result<(), NodeError> {
+    let parent = self.parent()?;
+
+    // set parent link, prev / next between selected node and node to insert
+    {
+      let mut inner = node.inner.write().unwrap();
+      inner.parent = Some(parent.downgrade());
+      inner.next = Some(self.clone());
+      self.inner.write().unwrap().prev = Some(node.clone());
+    }
+
+    node.touch();
+    parent.touch();
+
+    let i = self.get_index(&parent)?;
+    let mut children = parent.inner.write().unwrap();
+
+    // if there is a node in the children before the one we add, we set its next and set our prev
+    if i > 0 {
+      if let Some(prev) = children.children.get(i - 1) {
+        prev.inner.write().unwrap().next = Some(node.clone());
+        node.inner.write().unwrap().prev = Some(prev.clone());
+      }
+    }
+
+    children.children.insert(i, node);
+
+    Ok(())
+  }
+
+  pub fn insert_after(&self, node: Node) -> Result<(), NodeError> {
+    let parent = self.parent()?;
 
     // set parent link, prev / next between selected node and node to insert
     {
@@ -475,6 +1161,9 @@ impl Node {
       self.inner.write().unwrap().next = Some(node.clone());
     }
 
+    node.touch();
+    parent.touch();
+
     let i = self.get_index(&parent)? + 1;
     let mut children = parent.inner.write().unwrap();
 
@@ -511,11 +1200,51 @@ impl Node {
     if let Some(next) = next {
       next.inner.write().unwrap().prev = prev;
     }
+
+    // the node is not part of the tree anymore
+    {
+      let mut node = node.inner.write().unwrap();
+      node.parent = None;
+      node.prev = None;
+      node.next = None;
+    }
+
+    drop(inner);
+    self.touch();
     Ok(())
   }
 
+  /// Check whether this node is an ancestor of `node`, i.e. its parent, the parent of its parent, etc.
+  pub fn is_ancestor_of(&self, node: &Node) -> bool {
+    let mut current = node.parent().ok();
+
+    while let Some(node) = current {
+      if &node == self {
+        return true;
+      }
+
+      current = node.parent().ok();
+    }
+
+    false
+  }
+
+  /// Check that `node` can be moved inside this node, returning its current parent.
+  ///
+  /// The root node cannot be moved, and a node cannot be moved inside itself or one of its descendants, as that would
+  /// detach the whole subtree into a cycle.
+  pub(crate) fn check_move(&self, node: &Node) -> Result<Node, NodeError> {
+    let parent = node.parent().map_err(|_| NodeError::CannotMoveRoot)?;
+
+    if node == self || node.is_ancestor_of(self) {
+      return Err(NodeError::CyclicMove);
+    }
+
+    Ok(parent)
+  }
+
   pub fn move_top(&self, node: Node) -> Result<(), NodeError> {
-    let parent = node.parent()?;
+    let parent = self.check_move(&node)?;
 
     parent.delete(node.clone())?;
     self.insert_top(node);
@@ -523,7 +1252,7 @@ impl Node {
   }
 
   pub fn move_bottom(&self, node: Node) -> Result<(), NodeError> {
-    let parent = node.parent()?;
+    let parent = self.check_move(&node)?;
 
     parent.delete(node.clone())?;
     self.insert_bottom(node);
@@ -531,7 +1260,12 @@ impl Node {
   }
 
   pub fn move_before(&self, node: Node) -> Result<(), NodeError> {
-    let parent = node.parent()?;
+    let parent = self.parent()?.check_move(&node)?;
+
+    // moving a node next to itself doesn’t change anything
+    if &node == self {
+      return Ok(());
+    }
 
     parent.delete(node.clone())?;
     self.insert_before(node)?;
@@ -539,7 +1273,12 @@ impl Node {
   }
 
   pub fn move_after(&self, node: Node) -> Result<(), NodeError> {
-    let parent = node.parent()?;
+    let parent = self.parent()?.check_move(&node)?;
+
+    // moving a node next to itself doesn’t change anything
+    if &node == self {
+      return Ok(());
+    }
 
     parent.delete(node.clone())?;
     self.insert_after(node)?;
@@ -551,6 +1290,82 @@ impl Node {
     node.is_expanded = !node.is_expanded;
   }
 
+  /// Sort the children of the node, optionally in reverse order and recursively.
+  ///
+  /// The sort is stable: children comparing equal keep their relative order. Nodes whose children are already sorted
+  /// are left untouched, so that their modification date doesn’t change.
+  pub fn sort_children(&self, key: SortKey, reverse: bool, recursive: bool) {
+    let mut children = self.inner.read().unwrap().children.clone();
+    children.sort_by(|a, b| {
+      let ordering = key.compare(a, b);
+      if reverse {
+        ordering.reverse()
+      } else {
+        ordering
+      }
+    });
+
+    // the children are the same, just in another order
+    let _ = self.reorder_children(children.clone());
+
+    if recursive {
+      for child in children {
+        child.sort_children(key, reverse, recursive);
+      }
+    }
+  }
+
+  /// Reorder the children of the node; `children` must contain exactly the current children of the node.
+  ///
+  /// The node is touched only if the order changes.
+  pub(crate) fn reorder_children(&self, children: Vec<Node>) -> Result<(), NodeError> {
+    {
+      let inner = self.inner.read().unwrap();
+      if children.len() != inner.children.len()
+        || children.iter().any(|child| !inner.children.contains(child))
+      {
+        return Err(NodeError::NotContainedInParent);
+      }
+
+      if children == inner.children {
+        return Ok(());
+      }
+    }
+
+    self.link_children(children);
+    self.touch();
+    Ok(())
+  }
+
+  /// Repair the links between the node and its children.
+  ///
+  /// Children claimed by another parent which contains them are removed from the node; the parent and sibling links
+  /// of the other ones are reset.
+  pub(crate) fn repair_children(&self) {
+    let children: Vec<_> = self.children().into_iter().cloned().collect();
+    let children = children
+      .into_iter()
+      .filter(|child| match child.parent() {
+        Ok(parent) if &parent != self => !parent.inner.read().unwrap().children.contains(child),
+        _ => true,
+      })
+      .collect();
+
+    self.link_children(children);
+  }
+
+  /// Set the children of the node, resetting their parent and sibling links.
+  fn link_children(&self, children: Vec<Node>) {
+    for (i, child) in children.iter().enumerate() {
+      let mut inner = child.inner.write().unwrap();
+      inner.parent = Some(self.downgrade());
+      inner.prev = i.checked_sub(1).map(|i| children[i].clone());
+      inner.next = children.get(i + 1).cloned();
+    }
+
+    self.inner.write().unwrap().children = children;
+  }
+
   pub fn paths(&self, prefix: impl AsRef<str>, filter: NodeFilter) -> Vec<String> {
     let prefix = prefix.as_ref();
     let mut all_paths = Vec::new();
@@ -560,13 +1375,30 @@ impl Node {
     }
 
     let prefix = if prefix == "/" { "" } else { prefix };
-    self.paths_rec(prefix, &mut all_paths, filter);
+    self.paths_rec(prefix, &mut all_paths, &filter);
     all_paths
   }
 
-  fn paths_rec(&self, parent: &str, paths: &mut Vec<String>, filter: NodeFilter) {
-    for child in &self.inner.read().unwrap().children {
-      let path = format!("{parent}/{name}", name = child.name());
+  fn paths_rec(&self, parent: &str, paths: &mut Vec<String>, filter: &NodeFilter) {
+    let children = &self.inner.read().unwrap().children;
+
+    // siblings sharing a name are disambiguated with their index
+    let mut counts = HashMap::new();
+    for child in children {
+      *counts.entry(child.name()).or_insert(0) += 1;
+    }
+
+    let mut indices = HashMap::new();
+    for child in children {
+      let name = child.name();
+      let index = indices.entry(name.clone()).or_insert(0);
+      *index += 1;
+
+      let segment = PathSegment {
+        index: (counts[&name] > 1).then_some(*index),
+        name,
+      };
+      let path = format!("{parent}/{segment}");
 
       if filter.accepts(child) {
         paths.push(path.clone());
@@ -576,6 +1408,37 @@ impl Node {
     }
   }
 
+  /// All the nodes of the subtree rooted at this node, including itself, in depth-first order.
+  pub fn subtree(&self) -> Vec<Node> {
+    let mut nodes = vec![self.clone()];
+
+    for child in &self.inner.read().unwrap().children {
+      nodes.extend(child.subtree());
+    }
+
+    nodes
+  }
+
+  /// The `count` most recently modified nodes of the subtree rooted at this node, most recent first.
+  ///
+  /// Nodes with no modification time are ignored. Only the first occurrence of cloned nodes is returned.
+  pub fn recently_modified(&self, count: usize) -> Vec<Node> {
+    let mut seen = HashSet::new();
+    let mut nodes: Vec<_> = self
+      .subtree()
+      .into_iter()
+      .filter(|node| seen.insert(node.id()))
+      .filter_map(|node| node.modified_at().map(|time| (time, node)))
+      .collect();
+
+    nodes.sort_by(|(a, _), (b, _)| b.cmp(a));
+    nodes
+      .into_iter()
+      .take(count)
+      .map(|(_, node)| node)
+      .collect()
+  }
+
   /// Write paths to the provided writer.
   pub fn write_paths(
     &self,
@@ -593,85 +1456,510 @@ impl Node {
 
 #[derive(Clone, Debug)]
 pub struct NodeInner {
-  name: String,
-  icon: String,
+  content: Arc<RwLock<NodeContent>>,
   is_expanded: bool,
   parent: Option<WeakNode>,
   prev: Option<Node>,
   next: Option<Node>,
-  data: Option<NodeData>,
   children: Vec<Node>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum NodeData {
-  File(PathBuf),
-  Link(String),
+/// Content of a node, shared by all of its occurrences (clones).
+#[derive(Clone, Debug)]
+struct NodeContent {
+  id: NodeId,
+  name: String,
+  icon: String,
+  created_at: Option<DateTime<Utc>>,
+  modified_at: Option<DateTime<Utc>>,
+  tags: BTreeSet<String>,
+  properties: BTreeMap<String, String>,
+  status: Option<TaskStatus>,
+  body: Option<String>,
+  due: Option<NaiveDate>,
+  scheduled: Option<NaiveDate>,
+  attachments: Vec<NodeData>,
+
+  /// All the occurrences of the node, whether they are still in a tree or not.
+  occurrences: Vec<WeakNode>,
 }
 
-impl NodeData {
-  pub fn file(path: impl Into<PathBuf>) -> Self {
-    NodeData::File(path.into())
-  }
-
-  pub fn link(link: impl Into<String>) -> Self {
-    NodeData::Link(link.into())
+impl NodeContent {
+  fn from_encoding(node: &encoding::Node) -> Self {
+    Self {
+      id: node.id,
+      name: node.text.trim().to_owned(),
+      icon: node.icon.trim_start().to_owned(),
+      created_at: node.created_at,
+      modified_at: node.modified_at,
+      tags: node.tags.clone(),
+      properties: node.properties.clone(),
+      status: node.status,
+      body: node.body.clone(),
+      due: node.due,
+      scheduled: node.scheduled,
+      attachments: node.attachments.clone(),
+      occurrences: Vec::new(),
+    }
   }
-}
-
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
-pub enum NodeFilter {
-  #[default]
-  Always,
-  FileOrLink,
-  FileOnly,
-  LinkOnly,
-}
 
-impl NodeFilter {
-  pub fn new(file: bool, uri: bool) -> Self {
-    match (file, uri) {
-      (true, true) => Self::FileOrLink,
-      (true, false) => Self::FileOnly,
-      (false, true) => Self::LinkOnly,
-      (false, false) => Self::Always,
+  /// Create the content of all the nodes of an encoded tree, clones excluded.
+  fn collect_encoding(
+    node: &encoding::Node,
+    contents: &mut HashMap<NodeId, Arc<RwLock<NodeContent>>>,
+  ) {
+    if !node.is_clone {
+      contents
+        .entry(node.id)
+        .or_insert_with(|| Arc::new(RwLock::new(Self::from_encoding(node))));
     }
-  }
 
-  fn accepts(&self, node: &Node) -> bool {
-    match self {
-      NodeFilter::Always => true,
-      NodeFilter::FileOrLink => node.inner.read().unwrap().data.is_some(),
-      NodeFilter::FileOnly => matches!(node.inner.read().unwrap().data, Some(NodeData::File(..))),
-      NodeFilter::LinkOnly => matches!(node.inner.read().unwrap().data, Some(NodeData::Link(..))),
+    for child in &node.children {
+      Self::collect_encoding(child, contents);
     }
   }
 }
 
-/// An iterator on a node children.
-#[derive(Debug)]
-pub struct Children<'a> {
-  borrow: RwLockReadGuard<'a, NodeInner>,
-}
+/// Stable unique identifier of a [`Node`].
+///
+/// Contrary to paths and line numbers, an identifier doesn’t change when a node is renamed or moved around.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct NodeId(Uuid);
 
-impl<'a> Children<'a> {
-  pub fn into_iter(&'a self) -> impl Iterator<Item = &'_ Node> {
-    self.borrow.children.iter()
+impl NodeId {
+  /// Generate a new, random identifier.
+  pub fn new() -> Self {
+    NodeId(Uuid::new_v4())
   }
+}
 
-  pub fn all_except_last(&'a self) -> impl Iterator<Item = &'_ Node> {
-    self.borrow.children[..self.borrow.children.len() - 1].iter()
+impl Default for NodeId {
+  fn default() -> Self {
+    Self::new()
   }
+}
 
-  pub fn last(&'a self) -> Option<&'a Node> {
-    self.borrow.children.last()
+impl fmt::Display for NodeId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.0.fmt(f)
   }
 }
 
-/// A node cursor to ease moving around.
-#[derive(Clone, Debug)]
-pub struct Cursor {
-  node: Node,
+impl FromStr for NodeId {
+  type Err = NodeError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Uuid::parse_str(s.trim())
+      .map(NodeId)
+      .map_err(|_| NodeError::InvalidId(s.to_owned()))
+  }
+}
+
+/// Key to sort the children of a node by.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SortKey {
+  /// Name, case-insensitively, with numbers compared by value, e.g. `day 2` before `day 10`.
+  Name,
+
+  /// Icon.
+  Icon,
+
+  /// Kind of the first attachment: nodes without data first, then files, links and references.
+  Data,
+
+  /// Nodes with children first.
+  Children,
+
+  /// Date and time of the last modification, oldest first.
+  Date,
+
+  /// Task status, in the order of [`TaskStatus::cycle`]; regular nodes come last.
+  Status,
+}
+
+impl SortKey {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      SortKey::Name => "name",
+      SortKey::Icon => "icon",
+      SortKey::Data => "data",
+      SortKey::Children => "children",
+      SortKey::Date => "date",
+      SortKey::Status => "status",
+    }
+  }
+
+  fn compare(&self, a: &Node, b: &Node) -> Ordering {
+    match self {
+      SortKey::Name => {
+        let (a, b) = (a.name(), b.name());
+        natural_cmp(&a, &b).then_with(|| a.cmp(&b))
+      }
+
+      SortKey::Icon => a.icon().cmp(&b.icon()),
+
+      SortKey::Data => {
+        let rank = |node: &Node| match node.attachments().first() {
+          None => 0,
+          Some(NodeData::File(_)) => 1,
+          Some(NodeData::Link(_)) => 2,
+          Some(NodeData::Node(_)) => 3,
+        };
+        rank(a).cmp(&rank(b))
+      }
+
+      SortKey::Children => b.has_children().cmp(&a.has_children()),
+
+      SortKey::Date => a.modified_at().cmp(&b.modified_at()),
+
+      SortKey::Status => {
+        let rank = |node: &Node| match node.status() {
+          Some(TaskStatus::Todo) => 0,
+          Some(TaskStatus::Doing) => 1,
+          Some(TaskStatus::Done) => 2,
+          Some(TaskStatus::Cancelled) => 3,
+          None => 4,
+        };
+        rank(a).cmp(&rank(b))
+      }
+    }
+  }
+}
+
+impl fmt::Display for SortKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for SortKey {
+  type Err = NodeError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "name" => Ok(SortKey::Name),
+      "icon" => Ok(SortKey::Icon),
+      "data" => Ok(SortKey::Data),
+      "children" => Ok(SortKey::Children),
+      "date" => Ok(SortKey::Date),
+      "status" => Ok(SortKey::Status),
+      _ => Err(NodeError::UnknownSortKey(s.to_owned())),
+    }
+  }
+}
+
+/// Compare strings case-insensitively, with runs of digits compared by value.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+  let mut a = a.chars().peekable();
+  let mut b = b.chars().peekable();
+
+  loop {
+    let (x, y) = match (a.peek(), b.peek()) {
+      (None, None) => return Ordering::Equal,
+      (None, Some(_)) => return Ordering::Less,
+      (Some(_), None) => return Ordering::Greater,
+      (Some(x), Some(y)) => (*x, *y),
+    };
+
+    let ordering = if x.is_ascii_digit() && y.is_ascii_digit() {
+      let x = take_number(&mut a);
+      let y = take_number(&mut b);
+      x.len().cmp(&y.len()).then_with(|| x.cmp(&y))
+    } else {
+      a.next();
+      b.next();
+      x.to_lowercase().cmp(y.to_lowercase())
+    };
+
+    if ordering != Ordering::Equal {
+      return ordering;
+    }
+  }
+}
+
+/// Take the digits at the front of an iterator, without leading zeros.
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+  let mut number = String::new();
+
+  while let Some(c) = chars.next_if(char::is_ascii_digit) {
+    if c != '0' || !number.is_empty() {
+      number.push(c);
+    }
+  }
+
+  number
+}
+
+/// Status of a task node.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+  Todo,
+  Doing,
+  Done,
+  Cancelled,
+}
+
+impl TaskStatus {
+  /// Next status in the cycle `none → TODO → DOING → DONE → CANCELLED → none`.
+  pub fn cycle(status: Option<Self>) -> Option<Self> {
+    match status {
+      None => Some(TaskStatus::Todo),
+      Some(TaskStatus::Todo) => Some(TaskStatus::Doing),
+      Some(TaskStatus::Doing) => Some(TaskStatus::Done),
+      Some(TaskStatus::Done) => Some(TaskStatus::Cancelled),
+      Some(TaskStatus::Cancelled) => None,
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      TaskStatus::Todo => "TODO",
+      TaskStatus::Doing => "DOING",
+      TaskStatus::Done => "DONE",
+      TaskStatus::Cancelled => "CANCELLED",
+    }
+  }
+}
+
+impl fmt::Display for TaskStatus {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// Task progress of a subtree.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Progress {
+  /// Number of done tasks.
+  pub done: usize,
+
+  /// Number of tasks, not including cancelled ones.
+  pub total: usize,
+}
+
+impl Progress {
+  /// Account for a task with the given status; cancelled tasks and nodes without status are ignored.
+  fn add(&mut self, status: Option<TaskStatus>) {
+    match status {
+      Some(TaskStatus::Done) => {
+        self.done += 1;
+        self.total += 1;
+      }
+
+      Some(TaskStatus::Todo | TaskStatus::Doing) => self.total += 1,
+
+      Some(TaskStatus::Cancelled) | None => (),
+    }
+  }
+}
+
+impl fmt::Display for Progress {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}/{}", self.done, self.total)
+  }
+}
+
+/// Task progress of all the nodes of a subtree, computed in a single bottom-up pass.
+///
+/// [`SubtreeProgress::get`] returns the same as [`Node::progress`], without walking the subtree of each node. Progress
+/// is kept per occurrence, as occurrences of a cloned node have their own children.
+#[derive(Debug, Default)]
+pub struct SubtreeProgress {
+  progress: HashMap<*const RwLock<NodeInner>, Progress>,
+}
+
+impl SubtreeProgress {
+  pub fn new(root: &Node) -> Self {
+    let mut progress = Self::default();
+    progress.visit(root);
+    progress
+  }
+
+  /// Progress of a node of the subtree; nodes outside of it have no progress.
+  pub fn get(&self, node: &Node) -> Progress {
+    self
+      .progress
+      .get(&Arc::as_ptr(&node.inner))
+      .copied()
+      .unwrap_or_default()
+  }
+
+  /// Compute the progress of the node and its descendants.
+  ///
+  /// Return the progress of the tasks below the node that are not cloned, and the status of the cloned ones, which
+  /// are kept apart so that they are accounted for only once.
+  fn visit(&mut self, node: &Node) -> (Progress, HashMap<NodeId, TaskStatus>) {
+    let mut unique = Progress::default();
+    let mut cloned = HashMap::new();
+
+    for child in node.children().into_iter() {
+      let (child_unique, mut child_cloned) = self.visit(child);
+      unique.done += child_unique.done;
+      unique.total += child_unique.total;
+
+      if cloned.len() < child_cloned.len() {
+        mem::swap(&mut cloned, &mut child_cloned);
+      }
+      cloned.extend(child_cloned);
+
+      match child.status() {
+        Some(status) if child.is_cloned() => {
+          cloned.insert(child.id(), status);
+        }
+        status => unique.add(status),
+      }
+    }
+
+    let mut progress = unique;
+    for &status in cloned.values() {
+      progress.add(Some(status));
+    }
+    self.progress.insert(Arc::as_ptr(&node.inner), progress);
+
+    (unique, cloned)
+  }
+}
+
+/// Data attached to a node.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeData {
+  File(PathBuf),
+  Link(String),
+
+  /// Reference to another node of the same tree.
+  ///
+  /// If the referenced node is cloned, the reference resolves to its first occurrence.
+  Node(NodeId),
+}
+
+impl NodeData {
+  pub fn file(path: impl Into<PathBuf>) -> Self {
+    NodeData::File(path.into())
+  }
+
+  pub fn link(link: impl Into<String>) -> Self {
+    NodeData::Link(link.into())
+  }
+
+  pub fn node(id: NodeId) -> Self {
+    NodeData::Node(id)
+  }
+}
+
+impl fmt::Display for NodeData {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      NodeData::File(path) => path.display().fmt(f),
+      NodeData::Link(link) => f.write_str(link),
+      NodeData::Node(id) => write!(f, "id:{id}"),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum NodeFilter {
+  #[default]
+  Always,
+  FileOrLink,
+  FileOnly,
+  LinkOnly,
+
+  /// Nodes having the given tag.
+  Tag(String),
+
+  /// Nodes having the given property; if a value is provided, the property must have that value.
+  Property {
+    key: String,
+    value: Option<String>,
+  },
+
+  /// Nodes matching the given query.
+  Query(Query),
+}
+
+impl NodeFilter {
+  /// Create a property filter from a `key` or `key=value` string.
+  pub fn property(filter: &str) -> Self {
+    match filter.split_once('=') {
+      Some((key, value)) => NodeFilter::Property {
+        key: key.trim().to_owned(),
+        value: Some(value.to_owned()),
+      },
+
+      None => NodeFilter::Property {
+        key: filter.trim().to_owned(),
+        value: None,
+      },
+    }
+  }
+
+  pub fn new(file: bool, uri: bool) -> Self {
+    match (file, uri) {
+      (true, true) => Self::FileOrLink,
+      (true, false) => Self::FileOnly,
+      (false, true) => Self::LinkOnly,
+      (false, false) => Self::Always,
+    }
+  }
+
+  /// Whether an attachment is accepted by the filter.
+  ///
+  /// Filters that are not about data accept all attachments.
+  pub fn accepts_attachment(&self, data: &NodeData) -> bool {
+    match self {
+      NodeFilter::FileOnly => matches!(data, NodeData::File(..)),
+      NodeFilter::LinkOnly => matches!(data, NodeData::Link(..)),
+      _ => true,
+    }
+  }
+
+  fn accepts(&self, node: &Node) -> bool {
+    match self {
+      NodeFilter::Always => true,
+      NodeFilter::FileOrLink | NodeFilter::FileOnly | NodeFilter::LinkOnly => node
+        .content()
+        .read()
+        .unwrap()
+        .attachments
+        .iter()
+        .any(|data| self.accepts_attachment(data)),
+      NodeFilter::Tag(tag) => node.has_tag(tag),
+      NodeFilter::Query(query) => query.matches(node),
+      NodeFilter::Property { key, value } => match (node.property(key), value) {
+        (Some(prop), Some(value)) => &prop == value,
+        (prop, _) => prop.is_some(),
+      },
+    }
+  }
+}
+
+/// An iterator on a node children.
+#[derive(Debug)]
+pub struct Children<'a> {
+  borrow: RwLockReadGuard<'a, NodeInner>,
+}
+
+impl<'a> Children<'a> {
+  pub fn into_iter(&'a self) -> impl Iterator<Item = &'a Node> {
+    self.borrow.children.iter()
+  }
+
+  pub fn all_except_last(&'a self) -> impl Iterator<Item = &'a Node> {
+    self.borrow.children[..self.borrow.children.len() - 1].iter()
+  }
+
+  pub fn last(&'a self) -> Option<&'a Node> {
+    self.borrow.children.last()
+  }
+}
+
+/// A node cursor to ease moving around.
+#[derive(Clone, Debug)]
+pub struct Cursor {
+  node: Node,
 }
 
 impl Cursor {
@@ -798,11 +2086,14 @@ pub enum NodeError {
   #[error("cannot set name; name cannot be empty")]
   EmptyName,
 
-  #[error("cannot set data; file data already exists")]
-  FileDataAlreadyExists,
+  #[error("cannot attach data; already attached")]
+  AttachmentAlreadyExists,
+
+  #[error("a node cannot reference itself")]
+  SelfReference,
 
-  #[error("cannot set data; already exists with a different type")]
-  MismatchDataType,
+  #[error("no attachment at index {0}")]
+  NoSuchAttachment(usize),
 
   #[error("no data")]
   NoData,
@@ -812,35 +2103,262 @@ pub enum NodeError {
 
   #[error("cannot write paths")]
   CannotWritePaths(io::Error),
+
+  #[error("invalid node ID: {0}")]
+  InvalidId(String),
+
+  #[error("invalid tag '{0}'; tags cannot be empty or contain whitespace")]
+  InvalidTag(String),
+
+  #[error("invalid property key '{0}'; keys cannot be empty or contain '='")]
+  InvalidPropertyKey(String),
+
+  #[error("ambiguous path; {1} nodes are named '{0}', use '{0}[N]' to select one of them")]
+  AmbiguousPath(String, usize),
+
+  #[error("ambiguous ID; node {0} appears {1} times, use 'id:{0}[N]' to select one of them")]
+  AmbiguousId(NodeId, usize),
+
+  #[error("unknown sort key '{0}'; expected name, icon, data, children, date or status")]
+  UnknownSortKey(String),
+
+  #[error("cannot move the root node")]
+  CannotMoveRoot,
+
+  #[error("cannot move a node inside itself or one of its descendants")]
+  CyclicMove,
+}
+
+/// A segment of a path, selecting a child node.
+///
+/// Segments are written as the name of the node, escaped with [`escape_name`], optionally followed by `[N]`, the
+/// 1-based index of the node among its siblings having the same name, e.g. `todo[2]`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PathSegment {
+  /// Name of the node.
+  pub name: String,
+
+  /// Index of the node among its siblings with the same name, starting at 1.
+  pub index: Option<usize>,
+}
+
+impl From<&str> for PathSegment {
+  fn from(name: &str) -> Self {
+    name.to_owned().into()
+  }
+}
+
+impl From<String> for PathSegment {
+  fn from(name: String) -> Self {
+    Self { name, index: None }
+  }
+}
+
+impl fmt::Display for PathSegment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&escape_name(&self.name))?;
+
+    if let Some(index) = self.index {
+      write!(f, "[{index}]")?;
+    }
+
+    Ok(())
+  }
 }
 
 /// Split a string in the form of `/NodeA/NodeB/…` into an iterator of path segment.
 ///
+/// A backslash escapes the character following it, so that `\/` and `\\` stand for a literal slash and backslash inside
+/// a segment (see [`escape_name`]). A segment ending with an unescaped `[N]` selects the N-th node with that name (see
+/// [`PathSegment`]). Segments are trimmed, as node names are, and empty segments are ignored.
+///
 /// Use that function to pass to various tree and node API functions expecting a path.
-pub fn path_iter(path: &str) -> impl Iterator<Item = &str> {
-  path.split('/').filter(|frag| !frag.trim().is_empty())
+pub fn path_iter(path: &str) -> impl Iterator<Item = PathSegment> {
+  let mut segments = Vec::new();
+  let mut segment = String::new();
+
+  // position of the last unescaped opening bracket of the current segment, which might start an index
+  let mut bracket = None;
+  let mut chars = path.chars();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => {
+        segment.push(chars.next().unwrap_or('\\'));
+        bracket = None;
+      }
+
+      '/' => segments.push(parse_segment(mem::take(&mut segment), bracket.take())),
+
+      '[' => {
+        bracket = Some(segment.len());
+        segment.push(c);
+      }
+
+      _ => segment.push(c),
+    }
+  }
+
+  segments.push(parse_segment(segment, bracket));
+  segments
+    .into_iter()
+    .filter(|segment| !segment.name.is_empty())
+}
+
+/// Parse an unescaped segment, extracting its index, if any, starting at `bracket`.
+fn parse_segment(mut segment: String, bracket: Option<usize>) -> PathSegment {
+  let index = bracket.and_then(|bracket| {
+    segment[bracket..]
+      .trim_end()
+      .strip_prefix('[')?
+      .strip_suffix(']')
+      .filter(|index| index.chars().all(|c| c.is_ascii_digit()))?
+      .parse()
+      .ok()
+      .map(|index| (bracket, index))
+  });
+
+  if let Some((bracket, _)) = index {
+    segment.truncate(bracket);
+  }
+
+  PathSegment {
+    name: segment.trim().to_owned(),
+    index: index.map(|(_, index)| index),
+  }
+}
+
+/// Escape a node name so that it can be used as a path segment.
+///
+/// Slashes and backslashes are prefixed with a backslash, as well as the opening bracket of a trailing `[N]`, so that it
+/// is not mistaken for an index; [`path_iter`] reverts that escaping.
+pub fn escape_name(name: &str) -> Cow<'_, str> {
+  let index_like = name
+    .strip_suffix(']')
+    .and_then(|name| name.rsplit_once('['))
+    .filter(|(_, index)| !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()));
+
+  if !name.contains(['/', '\\']) && index_like.is_none() {
+    return name.into();
+  }
+
+  let escape = |name: &str| name.replace('\\', "\\\\").replace('/', "\\/");
+  match index_like {
+    Some((prefix, index)) => format!("{}\\[{index}]", escape(prefix)).into(),
+    None => escape(name).into(),
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use crate::{
+    data_file::DataFileStore,
     encoding::{self, TreeType, Version},
-    node::{Node, NodeData, NodeError, NodeFilter, Tree},
+    history::Operation,
+    node::{
+      Node, NodeData, NodeError, NodeFilter, NodeId, Progress, SortKey, SubtreeProgress,
+      TaskStatus, Tree,
+    },
   };
 
-  use super::path_iter;
+  use super::{escape_name, natural_cmp, path_iter, PathSegment};
+  use proptest::{prelude::*, sample::Index};
+  use std::{env, fs};
+
+  fn names(path: &str) -> Vec<String> {
+    path_iter(path).map(|segment| segment.name).collect()
+  }
 
   #[test]
   fn path_iter_test() {
-    assert_eq!(path_iter("").collect::<Vec<_>>(), Vec::<&str>::new());
-    assert_eq!(path_iter("/").collect::<Vec<_>>(), Vec::<&str>::new());
-    assert_eq!(path_iter("//").collect::<Vec<_>>(), Vec::<&str>::new());
+    assert_eq!(names(""), Vec::<&str>::new());
+    assert_eq!(names("/"), Vec::<&str>::new());
+    assert_eq!(names("//"), Vec::<&str>::new());
+    assert_eq!(names("/Foo/Bar/Zoo"), vec!["Foo", "Bar", "Zoo"]);
+
+    assert_eq!(names("/A B"), vec!["A B"]);
+    assert_eq!(names("/ A B / C "), vec!["A B", "C"]);
+  }
+
+  #[test]
+  fn path_iter_escaping() {
+    assert_eq!(names(r"/CI\/CD/a\\b"), vec!["CI/CD", r"a\b"]);
+    assert_eq!(names(r"/a\"), vec![r"a\"]);
+    assert_eq!(escape_name("CI/CD"), r"CI\/CD");
+    assert_eq!(escape_name(r"a\/b"), r"a\\\/b");
+    assert_eq!(escape_name("plain"), "plain");
+  }
+
+  #[test]
+  fn path_iter_index() {
+    let segment = |name: &str, index| PathSegment {
+      name: name.to_owned(),
+      index,
+    };
+
     assert_eq!(
-      path_iter("/Foo/Bar/Zoo").collect::<Vec<_>>(),
-      vec!["Foo", "Bar", "Zoo"]
+      path_iter("/Inbox/todo[2]").collect::<Vec<_>>(),
+      vec![segment("Inbox", None), segment("todo", Some(2))]
     );
+    assert_eq!(
+      path_iter("/todo [12] /[WIP] x/a[b]").collect::<Vec<_>>(),
+      vec![
+        segment("todo", Some(12)),
+        segment("[WIP] x", None),
+        segment("a[b]", None)
+      ]
+    );
+
+    // escaped brackets are part of the name
+    assert_eq!(
+      path_iter(r"/v\[2]").collect::<Vec<_>>(),
+      vec![segment("v[2]", None)]
+    );
+    assert_eq!(escape_name("v[2]"), r"v\[2]");
+    assert_eq!(escape_name("[WIP] x"), "[WIP] x");
+    assert_eq!(segment("a/b", Some(3)).to_string(), r"a\/b[3]");
+  }
+
+  #[test]
+  fn path_escaping_roundtrip() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let names = [
+      "CI/CD",
+      r"back\slash",
+      "  spaced out  ",
+      r"a\/b/",
+      "/",
+      "v[2]",
+    ];
+
+    for name in names {
+      let node = Node::new(name, "");
+      root.insert_bottom(node.clone());
+
+      let child = Node::new(name, "");
+      node.insert_bottom(child.clone());
+
+      for node in [node, child] {
+        assert_eq!(
+          tree
+            .get_node_by_path(path_iter(&node.path()), false)
+            .unwrap(),
+          Some(node)
+        );
+      }
+    }
+
+    let paths = root.paths("/", NodeFilter::default());
+    assert_eq!(paths.len(), 1 + 2 * names.len());
 
-    assert_eq!(path_iter("/A B").collect::<Vec<_>>(), vec!["A B"]);
+    for path in paths {
+      let node = tree
+        .get_node_by_path(path_iter(&path), false)
+        .unwrap()
+        .unwrap();
+      assert_eq!(node.path(), path);
+    }
   }
 
   #[test]
@@ -855,9 +2373,8 @@ mod tests {
       tree
         .get_node_by_line(0)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("root")
     );
     assert_eq!(tree.get_node_by_line(1), None);
@@ -873,18 +2390,16 @@ mod tests {
       tree
         .get_node_by_line(0)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("root")
     );
     assert_eq!(
       tree
         .get_node_by_line(0)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("root")
     );
     assert_eq!(tree.get_node_by_line(1), None);
@@ -931,45 +2446,40 @@ mod tests {
       tree
         .get_node_by_line(0)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("root")
     );
     assert_eq!(
       tree
         .get_node_by_line(1)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("a")
     );
     assert_eq!(
       tree
         .get_node_by_line(2)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("b")
     );
     assert_eq!(
       tree
         .get_node_by_line(3)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("z")
     );
     assert_eq!(
       tree
         .get_node_by_line(4)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("c")
     );
   }
@@ -986,12 +2496,11 @@ mod tests {
       tree
         .get_node_by_line(0)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("root")
     );
-    assert_eq!(tree.get_node_by_path(["test"], false), None);
+    assert_eq!(tree.get_node_by_path(["test"], false).unwrap(), None);
 
     let tree = Tree::from_encoding(encoding::Tree {
       version: Version::default(),
@@ -1003,12 +2512,11 @@ mod tests {
       tree
         .get_node_by_line(0)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("root")
     );
-    assert_eq!(tree.get_node_by_path(["test"], false), None);
+    assert_eq!(tree.get_node_by_path(["test"], false).unwrap(), None);
   }
 
   // this tests a couple of queries on this tree:
@@ -1051,63 +2559,62 @@ mod tests {
       tree
         .get_node_by_line(0)
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("root")
     );
     assert_eq!(
       tree
         .get_node_by_path(["a"], false)
+        .unwrap()
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("a")
     );
     assert_eq!(
       tree
         .get_node_by_path(["a", "x"], false)
+        .unwrap()
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("x")
     );
     assert_eq!(
       tree
         .get_node_by_path(["a", "y"], false)
+        .unwrap()
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("y")
     );
     assert_eq!(
       tree
         .get_node_by_path(["b"], false)
+        .unwrap()
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("b")
     );
     assert_eq!(
       tree
         .get_node_by_path(["b", "z"], false)
+        .unwrap()
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("z")
     );
     assert_eq!(
       tree
         .get_node_by_path(["c"], false)
+        .unwrap()
         .as_ref()
-        .map(|node| node.inner.read().unwrap())
-        .as_ref()
-        .map(|node| node.name.as_str()),
+        .map(|node| node.name())
+        .as_deref(),
       Some("c")
     );
   }
@@ -1143,6 +2650,7 @@ mod tests {
       tree
         .get_node_by_path(["a", "x"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(0)
     ));
@@ -1150,6 +2658,7 @@ mod tests {
       tree
         .get_node_by_path(["a", "y"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(1)
     ));
@@ -1170,12 +2679,14 @@ mod tests {
     tree
       .get_node_by_path(["c"], false)
       .unwrap()
+      .unwrap()
       .insert_after(Node::new("d", ""))
       .unwrap();
 
     tree
       .get_node_by_path(["x"], false)
       .unwrap()
+      .unwrap()
       .insert_before(Node::new("w", ""))
       .unwrap();
 
@@ -1183,6 +2694,7 @@ mod tests {
       tree
         .get_node_by_path(["a"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(0)
     ));
@@ -1190,6 +2702,7 @@ mod tests {
       tree
         .get_node_by_path(["b"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(1)
     ));
@@ -1197,6 +2710,7 @@ mod tests {
       tree
         .get_node_by_path(["c"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(2)
     ));
@@ -1204,6 +2718,7 @@ mod tests {
       tree
         .get_node_by_path(["d"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(3)
     ));
@@ -1211,6 +2726,7 @@ mod tests {
       tree
         .get_node_by_path(["w"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(4)
     ));
@@ -1218,6 +2734,7 @@ mod tests {
       tree
         .get_node_by_path(["x"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(5)
     ));
@@ -1225,6 +2742,7 @@ mod tests {
       tree
         .get_node_by_path(["y"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(6)
     ));
@@ -1232,6 +2750,7 @@ mod tests {
       tree
         .get_node_by_path(["z"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(7)
     ));
@@ -1245,15 +2764,15 @@ mod tests {
     node.insert_bottom(Node::new("x", ""));
     node.insert_bottom(Node::new("y", ""));
 
-    let x = tree.get_node_by_path(["x"], false).unwrap();
+    let x = tree.get_node_by_path(["x"], false).unwrap().unwrap();
     x.insert_bottom(Node::new("a", ""));
     x.insert_bottom(Node::new("b", ""));
     x.insert_bottom(Node::new("c", ""));
 
-    let b = tree.get_node_by_path(["x", "b"], false).unwrap();
+    let b = tree.get_node_by_path(["x", "b"], false).unwrap().unwrap();
     x.delete(b).unwrap();
 
-    assert_eq!(tree.get_node_by_path(["x", "b"], false), None);
+    assert_eq!(tree.get_node_by_path(["x", "b"], false).unwrap(), None);
   }
 
   #[test]
@@ -1268,12 +2787,12 @@ mod tests {
     node.insert_top(Node::new("b", ""));
     node.insert_top(Node::new("a", ""));
 
-    let a = tree.get_node_by_path(["a"], false).unwrap();
-    let b = tree.get_node_by_path(["b"], false).unwrap();
-    let c = tree.get_node_by_path(["c"], false).unwrap();
-    let x = tree.get_node_by_path(["x"], false).unwrap();
-    let y = tree.get_node_by_path(["y"], false).unwrap();
-    let z = tree.get_node_by_path(["z"], false).unwrap();
+    let a = tree.get_node_by_path(["a"], false).unwrap().unwrap();
+    let b = tree.get_node_by_path(["b"], false).unwrap().unwrap();
+    let c = tree.get_node_by_path(["c"], false).unwrap().unwrap();
+    let x = tree.get_node_by_path(["x"], false).unwrap().unwrap();
+    let y = tree.get_node_by_path(["y"], false).unwrap().unwrap();
+    let z = tree.get_node_by_path(["z"], false).unwrap().unwrap();
 
     a.move_bottom(x.clone()).unwrap();
     a.move_top(y).unwrap();
@@ -1285,6 +2804,7 @@ mod tests {
       tree
         .get_node_by_path(["a", "y"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(0)
     ));
@@ -1292,6 +2812,7 @@ mod tests {
       tree
         .get_node_by_path(["a", "x"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(1)
     ));
@@ -1299,6 +2820,7 @@ mod tests {
       tree
         .get_node_by_path(["a", "b"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(2)
     ));
@@ -1306,6 +2828,7 @@ mod tests {
       tree
         .get_node_by_path(["a", "z"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(3)
     ));
@@ -1313,6 +2836,7 @@ mod tests {
       tree
         .get_node_by_path(["c"], false)
         .unwrap()
+        .unwrap()
         .get_index_from_parent(),
       Ok(1)
     ));
@@ -1326,7 +2850,7 @@ mod tests {
     node.insert_bottom(Node::new("x", ""));
     node.insert_bottom(Node::new("y", ""));
 
-    let x = tree.get_node_by_path(["x"], false).unwrap();
+    let x = tree.get_node_by_path(["x"], false).unwrap().unwrap();
     x.insert_bottom(Node::new("a", ""));
     x.insert_bottom(Node::new("b", ""));
     x.insert_bottom(Node::new("c", ""));
@@ -1341,23 +2865,880 @@ mod tests {
   fn data() {
     let node = Node::new("test", "");
 
-    assert_eq!(node.data(), None);
+    assert!(node.attachments().is_empty());
 
     assert!(matches!(
-      node.set_data(NodeData::file("/tmp/foo.md")),
+      node.add_attachment(NodeData::file("/tmp/foo.md")),
+      Ok(())
+    ));
+    assert!(matches!(
+      node.add_attachment(NodeData::file("/tmp/bar.rs")),
+      Ok(())
+    ));
+    assert!(matches!(
+      node.add_attachment(NodeData::link("https://foo.bar")),
       Ok(())
     ));
-    assert_eq!(node.data(), Some(NodeData::file("/tmp/foo.md")));
+    assert_eq!(
+      node.attachments(),
+      vec![
+        NodeData::file("/tmp/foo.md"),
+        NodeData::file("/tmp/bar.rs"),
+        NodeData::link("https://foo.bar")
+      ]
+    );
+
+    assert!(matches!(
+      node.add_attachment(NodeData::file("/tmp/foo.md")),
+      Err(NodeError::AttachmentAlreadyExists)
+    ));
+    assert!(matches!(
+      node.add_attachment(NodeData::link("")),
+      Err(NodeError::NoData)
+    ));
+
+    assert_eq!(
+      node.remove_attachment(1).unwrap(),
+      NodeData::file("/tmp/bar.rs")
+    );
+    assert!(matches!(
+      node.remove_attachment(2),
+      Err(NodeError::NoSuchAttachment(2))
+    ));
+    assert_eq!(node.attachments().len(), 2);
+  }
+
+  #[test]
+  fn get_node_by_id() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+
+    let x = Node::new("x", "");
+    let y = Node::new("y", "");
+    root.insert_bottom(x.clone());
+    root.insert_bottom(y.clone());
+
+    let id = y.id();
+    assert_eq!(tree.get_node_by_id(root.id()), Some(root.clone()));
+    assert_eq!(tree.get_node_by_id(id), Some(y.clone()));
+    assert_eq!(tree.get_node_by_id(NodeId::new()), None);
+
+    // the ID survives renames and moves
+    y.set_name("z").unwrap();
+    x.move_bottom(y.clone()).unwrap();
+    assert_eq!(tree.get_node_by_id(id), Some(y.clone()));
+    assert_eq!(y.path(), "/x/z");
+  }
+
+  #[test]
+  fn id_encoding_roundtrip() {
+    let tree = Tree::new("root", "");
+    let x = Node::new("x", "");
+    tree.root().insert_bottom(x.clone());
+
+    let decoded = Tree::from_encoding(tree.into_encoding());
+    assert_eq!(decoded.root().id(), tree.root().id());
+    assert_eq!(
+      decoded.get_node_by_id(x.id()).map(|node| node.name()),
+      Some("x".to_owned())
+    );
+  }
+
+  #[test]
+  fn node_id_parse() {
+    let id = NodeId::new();
+    assert_eq!(id.to_string().parse::<NodeId>().unwrap(), id);
+    assert!(matches!(
+      "nope".parse::<NodeId>(),
+      Err(NodeError::InvalidId(_))
+    ));
+  }
+
+  #[test]
+  fn timestamps() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let created_at = root.created_at().unwrap();
+    assert_eq!(root.modified_at(), Some(created_at));
+
+    let x = Node::new("x", "");
+    root.insert_bottom(x.clone());
+    let root_modified_at = root.modified_at().unwrap();
+    assert!(root_modified_at >= created_at);
+
+    x.set_name("y").unwrap();
+    assert!(x.modified_at().unwrap() >= root_modified_at);
+    assert_eq!(root.created_at(), Some(created_at));
+
+    // timestamps are persisted
+    let decoded = Tree::from_encoding(tree.into_encoding());
+    let decoded_x = decoded.get_node_by_id(x.id()).unwrap();
+    assert_eq!(decoded_x.created_at(), x.created_at());
+    assert_eq!(decoded_x.modified_at(), x.modified_at());
+  }
+
+  #[test]
+  fn timestamps_unknown() {
+    let node = Node::from_encoding(encoding::Node::new_by_expand_state("x", false, vec![]));
+    assert_eq!(node.created_at(), None);
+    assert_eq!(node.modified_at(), None);
+
+    node.set_icon("+");
+    assert_eq!(node.created_at(), None);
+    assert!(node.modified_at().is_some());
+  }
+
+  #[test]
+  fn recently_modified() {
+    let tree = Tree::from_encoding(encoding::Tree {
+      version: Version::default(),
+      ty: TreeType::Root,
+      node: encoding::Node::new_by_expand_state(
+        "root",
+        false,
+        vec![
+          encoding::Node::new_by_expand_state("a", false, vec![]),
+          encoding::Node::new_by_expand_state("b", false, vec![]),
+          encoding::Node::new_by_expand_state("c", false, vec![]),
+        ],
+      ),
+    });
+
+    let a = tree.get_node_by_path(["a"], false).unwrap().unwrap();
+    let c = tree.get_node_by_path(["c"], false).unwrap().unwrap();
+    c.set_name("c2").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(2));
+    a.set_name("a2").unwrap();
+
+    let recent = tree.root().recently_modified(10);
+    assert_eq!(recent, vec![a.clone(), c]);
+    assert_eq!(tree.root().recently_modified(1), vec![a]);
+  }
+
+  #[test]
+  fn tags() {
+    let node = Node::new("test", "");
+
+    assert!(node.tags().is_empty());
+    assert!(node.add_tag("oncall").unwrap());
+    assert!(!node.add_tag("#oncall").unwrap());
+    assert!(node.add_tag("#backend").unwrap());
+    assert!(matches!(node.add_tag("#"), Err(NodeError::InvalidTag(_))));
+    assert!(matches!(node.add_tag("a b"), Err(NodeError::InvalidTag(_))));
+
+    assert_eq!(
+      node.tags().into_iter().collect::<Vec<_>>(),
+      vec!["backend", "oncall"]
+    );
+    assert!(node.has_tag("#oncall"));
+
+    assert!(node.remove_tag("#backend"));
+    assert!(!node.remove_tag("backend"));
+    assert!(!node.has_tag("backend"));
+
+    let decoded = Node::from_encoding(node.into_encoding());
+    assert_eq!(decoded.tags(), node.tags());
+  }
+
+  #[test]
+  fn tag_filter() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(b.clone());
+    a.insert_bottom(Node::new("x", ""));
+    b.insert_bottom(Node::new("y", ""));
+
+    tree
+      .get_node_by_path(["a", "x"], false)
+      .unwrap()
+      .unwrap()
+      .add_tag("oncall")
+      .unwrap();
+    b.add_tag("oncall").unwrap();
+
+    assert_eq!(
+      root.paths("/", NodeFilter::Tag("oncall".to_owned())),
+      vec!["/a/x", "/b"]
+    );
+  }
+
+  #[test]
+  fn properties() {
+    let node = Node::new("test", "");
+
+    assert_eq!(node.property("owner"), None);
+    assert_eq!(node.set_property("owner", "alice").unwrap(), None);
+    assert_eq!(
+      node.set_property(" owner ", "bob").unwrap(),
+      Some("alice".to_owned())
+    );
+    assert_eq!(node.set_property("estimate", "3").unwrap(), None);
+    assert!(matches!(
+      node.set_property(" ", "x"),
+      Err(NodeError::InvalidPropertyKey(_))
+    ));
+    assert!(matches!(
+      node.set_property("a=b", "x"),
+      Err(NodeError::InvalidPropertyKey(_))
+    ));
+
+    assert_eq!(
+      node.properties().into_iter().collect::<Vec<_>>(),
+      vec![
+        ("estimate".to_owned(), "3".to_owned()),
+        ("owner".to_owned(), "bob".to_owned())
+      ]
+    );
+
+    let decoded = Node::from_encoding(node.into_encoding());
+    assert_eq!(decoded.properties(), node.properties());
+
+    assert_eq!(node.unset_property("estimate"), Some("3".to_owned()));
+    assert_eq!(node.unset_property("estimate"), None);
+  }
+
+  #[test]
+  fn property_filter() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(b.clone());
+    a.set_property("owner", "alice").unwrap();
+    b.set_property("owner", "bob").unwrap();
+
+    assert_eq!(
+      root.paths("/", NodeFilter::property("owner")),
+      vec!["/a", "/b"]
+    );
+    assert_eq!(
+      root.paths("/", NodeFilter::property("owner=bob")),
+      vec!["/b"]
+    );
+    assert!(root.paths("/", NodeFilter::property("ticket")).is_empty());
+  }
+
+  #[test]
+  fn status_cycle() {
+    let node = Node::new("task", "");
+
+    assert_eq!(node.status(), None);
+    assert_eq!(node.cycle_status(), Some(TaskStatus::Todo));
+    assert_eq!(node.cycle_status(), Some(TaskStatus::Doing));
+    assert_eq!(node.cycle_status(), Some(TaskStatus::Done));
+    assert_eq!(node.cycle_status(), Some(TaskStatus::Cancelled));
+    assert_eq!(node.cycle_status(), None);
+
+    node.set_status(Some(TaskStatus::Doing));
+    let decoded = Node::from_encoding(node.into_encoding());
+    assert_eq!(decoded.status(), Some(TaskStatus::Doing));
+  }
+
+  #[test]
+  fn progress() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let project = Node::new("project", "");
+    let sprint = Node::new("sprint", "");
+    root.insert_bottom(project.clone());
+    project.insert_bottom(sprint.clone());
+
+    let statuses = [
+      Some(TaskStatus::Done),
+      Some(TaskStatus::Todo),
+      Some(TaskStatus::Cancelled),
+      None,
+    ];
+    for (i, status) in statuses.into_iter().enumerate() {
+      let task = Node::new(format!("task {i}"), "");
+      task.set_status(status);
+      sprint.insert_bottom(task);
+    }
+
+    let doing = Node::new("doing", "");
+    doing.set_status(Some(TaskStatus::Doing));
+    project.insert_bottom(doing.clone());
+    let done = Node::new("done", "");
+    done.set_status(Some(TaskStatus::Done));
+    doing.insert_bottom(done);
+
+    assert_eq!(sprint.progress(), Progress { done: 1, total: 2 });
+    assert_eq!(doing.progress(), Progress { done: 1, total: 1 });
+    assert_eq!(project.progress(), Progress { done: 2, total: 4 });
+    assert_eq!(root.progress().to_string(), "2/4");
+    assert_subtree_progress(&root);
+  }
+
+  /// Check that the progress computed in a single pass matches the progress of each node.
+  fn assert_subtree_progress(root: &Node) {
+    let progress = SubtreeProgress::new(root);
+    for node in root.subtree() {
+      assert_eq!(progress.get(&node), node.progress(), "{}", node.path());
+    }
+  }
+
+  #[test]
+  fn body() {
+    let node = Node::new("note", "");
+    assert_eq!(node.body(), None);
+
+    node.set_body(Some("first line\nsecond line\n\n"));
+    assert_eq!(node.body().as_deref(), Some("first line\nsecond line"));
+
+    let decoded = Node::from_encoding(node.into_encoding());
+    assert_eq!(decoded.body(), node.body());
+
+    node.set_body(Some("  \n"));
+    assert_eq!(node.body(), None);
+  }
+
+  #[test]
+  fn backlinks() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let design = Node::new("design", "");
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    root.insert_bottom(design.clone());
+    root.insert_bottom(a.clone());
+    a.insert_bottom(b.clone());
+
+    a.add_attachment(NodeData::node(design.id())).unwrap();
+    b.add_attachment(NodeData::link("https://foo.bar")).unwrap();
+    b.add_attachment(NodeData::node(design.id())).unwrap();
+
+    assert!(matches!(
+      design.add_attachment(NodeData::node(design.id())),
+      Err(NodeError::SelfReference)
+    ));
+    assert_eq!(tree.backlinks(design.id()), vec![a.clone(), b]);
+    assert!(tree.backlinks(a.id()).is_empty());
+
+    let decoded = Node::from_encoding(a.into_encoding());
+    assert_eq!(decoded.attachments(), vec![NodeData::node(design.id())]);
+  }
+
+  #[test]
+  fn clones() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    let x = Node::new("x", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(b.clone());
+    a.insert_bottom(x.clone());
+    assert!(!x.is_cloned());
+
+    let y = x.new_clone();
+    assert!(!x.is_cloned());
+    b.insert_bottom(y.clone());
+    assert!(x.is_cloned());
+    assert!(y.is_clone_of(&x));
+    assert_eq!(x.clones(), vec![y.clone()]);
+    assert_eq!(y.id(), x.id());
+    assert_eq!(y.path(), "/b/x");
+
+    // edits are visible through all the occurrences
+    y.set_name("z").unwrap();
+    x.set_status(Some(TaskStatus::Done));
+    assert_eq!(x.path(), "/a/z");
+    assert_eq!(y.status(), Some(TaskStatus::Done));
+
+    // children and expanded state are specific to each occurrence
+    y.insert_bottom(Node::new("child", ""));
+    y.set_expanded(true);
+    assert!(!x.has_children());
+    assert!(!x.is_expanded());
+
+    // cloned tasks are accounted for once
+    assert_eq!(root.progress(), Progress { done: 1, total: 1 });
+    let task = Node::new("task", "");
+    task.set_status(Some(TaskStatus::Todo));
+    y.insert_bottom(task);
+    assert_eq!(root.progress(), Progress { done: 1, total: 2 });
+    assert_eq!(x.progress(), Progress::default());
+    assert_subtree_progress(&root);
+
+    // occurrences share their ID, so they must be told apart when selected by ID
+    assert_eq!(tree.get_nodes_by_id(x.id()), vec![x.clone(), y.clone()]);
+    assert!(matches!(
+      tree.select_node_by_id(&x.id().to_string()),
+      Err(NodeError::AmbiguousId(id, 2)) if id == x.id()
+    ));
+    assert_eq!(
+      tree.select_node_by_id(&format!("{}[2]", x.id())).unwrap(),
+      Some(y.clone())
+    );
+    assert_eq!(
+      tree.select_node_by_id(&format!("{}[3]", x.id())).unwrap(),
+      None
+    );
+    assert!(tree.select_node_by_id(&format!("{}[x]", x.id())).is_err());
+
+    // deleting an occurrence keeps the other ones
+    a.delete(x.clone()).unwrap();
+    assert!(!y.is_cloned());
+    assert_eq!(y.name(), "z");
+    assert_eq!(tree.get_node_by_id(y.id()), Some(y.clone()));
+    assert_eq!(
+      tree.select_node_by_id(&y.id().to_string()).unwrap(),
+      Some(y)
+    );
+  }
+
+  #[test]
+  fn clones_encoding_roundtrip() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let x = Node::new("x", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(x.clone());
+    x.add_tag("foo").unwrap();
+    a.insert_bottom(x.new_clone());
+
+    // only the first occurrence, in depth-first order, is fully encoded
+    let encoded = tree.into_encoding();
+    let original = &encoded.node.children[0].children[0];
+    let stub = &encoded.node.children[1];
+    assert!(stub.is_clone);
+    assert!(stub.text.is_empty() && stub.tags.is_empty());
+    assert!(!original.is_clone);
+    assert_eq!(original.text, "x");
+    assert_eq!(stub.id, original.id);
+
+    let decoded = Tree::from_encoding(encoded);
+    let decoded_x = decoded.get_node_by_path(["x"], false).unwrap().unwrap();
+    let decoded_clone = decoded
+      .get_node_by_path(["a", "x"], false)
+      .unwrap()
+      .unwrap();
+    assert!(decoded_clone.is_clone_of(&decoded_x));
+    assert!(decoded_x.has_tag("foo"));
+
+    decoded_x.set_name("y").unwrap();
+    assert_eq!(decoded_clone.name(), "y");
+  }
+
+  #[test]
+  fn duplicate_siblings() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let inbox = Node::new("Inbox", "");
+    let first = Node::new("todo", "");
+    let other = Node::new("other", "");
+    let second = Node::new("todo", "");
+    root.insert_bottom(inbox.clone());
+    inbox.insert_bottom(first.clone());
+    inbox.insert_bottom(other.clone());
+    inbox.insert_bottom(second.clone());
+
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      vec![
+        "/",
+        "/Inbox",
+        "/Inbox/todo[1]",
+        "/Inbox/other",
+        "/Inbox/todo[2]"
+      ]
+    );
+    assert_eq!(first.path(), "/Inbox/todo[1]");
+    assert_eq!(second.path(), "/Inbox/todo[2]");
+
+    assert_eq!(
+      tree
+        .get_node_by_path(path_iter("/Inbox/todo[2]"), false)
+        .unwrap(),
+      Some(second.clone())
+    );
+    assert_eq!(
+      tree
+        .get_node_by_path(path_iter("/Inbox/other[1]"), false)
+        .unwrap(),
+      Some(other)
+    );
+    assert_eq!(
+      tree
+        .get_node_by_path(path_iter("/Inbox/todo[3]"), true)
+        .unwrap(),
+      None
+    );
+    assert!(matches!(
+      tree.get_node_by_path(path_iter("/Inbox/todo"), true),
+      Err(NodeError::AmbiguousPath(name, 2)) if name == "todo"
+    ));
+
+    // once the collision is gone, the index is not needed anymore, but still accepted
+    inbox.delete(first).unwrap();
+    assert_eq!(second.path(), "/Inbox/todo");
+    assert_eq!(
+      tree
+        .get_node_by_path(path_iter("/Inbox/todo[1]"), false)
+        .unwrap(),
+      Some(second)
+    );
+  }
 
+  #[test]
+  fn transaction() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(b.clone());
+
+    // the first failing operation rolls back everything
+    let mut transaction = tree.transaction();
+    transaction
+      .apply(Operation::insert_top(a.clone(), Node::new("x", "")))
+      .unwrap();
+    transaction
+      .apply(Operation::move_bottom(a.clone(), b.clone()))
+      .unwrap();
     assert!(matches!(
-      node.set_data(NodeData::file("/tmp/bar.rs")),
-      Err(NodeError::FileDataAlreadyExists)
+      transaction.apply(Operation::Rename {
+        node: a.clone(),
+        name: String::new(),
+      }),
+      Err(NodeError::EmptyName)
     ));
-    assert_eq!(node.data(), Some(NodeData::file("/tmp/foo.md")));
+    drop(transaction);
+    assert_eq!(root.paths("/", NodeFilter::default()), ["/", "/a", "/b"]);
+
+    // so does an operation that cannot even be created, such as inserting before the root
+    let batch = || -> Result<(), NodeError> {
+      let mut transaction = tree.transaction();
+      transaction.apply(Operation::insert_top(a.clone(), Node::new("x", "")))?;
+      transaction.apply(Operation::move_bottom(a.clone(), b.clone()))?;
+      transaction.apply(Operation::insert_before(&root, Node::new("y", ""))?)?;
+      transaction.commit();
+      Ok(())
+    };
+    assert!(matches!(batch(), Err(NodeError::NoParent)));
+    assert_eq!(root.paths("/", NodeFilter::default()), ["/", "/a", "/b"]);
+    assert!(tree.undo().unwrap().is_none());
+
+    // dropping a transaction rolls it back as well
+    let mut transaction = tree.transaction();
+    transaction
+      .apply(Operation::Delete { node: b.clone() })
+      .unwrap();
+    drop(transaction);
+    assert_eq!(root.paths("/", NodeFilter::default()), ["/", "/a", "/b"]);
+
+    // a committed transaction is undone as a whole
+    let mut transaction = tree.transaction();
+    transaction
+      .apply(Operation::move_before(&a, b.clone()).unwrap())
+      .unwrap();
+    transaction
+      .apply(Operation::insert_after(&a, Node::new("c", "")).unwrap())
+      .unwrap();
+    transaction.commit();
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      ["/", "/b", "/a", "/c"]
+    );
+
+    tree.undo().unwrap();
+    assert_eq!(root.paths("/", NodeFilter::default()), ["/", "/a", "/b"]);
+    tree.redo().unwrap();
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      ["/", "/b", "/a", "/c"]
+    );
+  }
+
+  #[test]
+  fn natural_order() {
+    let mut names = vec!["day 10", "Day 2", "day 1", "day 02b", "day", "apple"];
+    names.sort_by(|a, b| natural_cmp(a, b));
+    assert_eq!(
+      names,
+      ["apple", "day", "day 1", "Day 2", "day 02b", "day 10"]
+    );
+  }
+
+  #[test]
+  fn sort_children() {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let b10 = Node::new("b10", "");
+    let b2 = Node::new("b2", "");
+    let a = Node::new("a", "");
+    let z = Node::new("z", "");
+    let y = Node::new("y", "");
+    root.insert_bottom(b10.clone());
+    root.insert_bottom(b2.clone());
+    root.insert_bottom(a.clone());
+    a.insert_bottom(z.clone());
+    a.insert_bottom(y.clone());
+    b2.set_status(Some(TaskStatus::Done));
+    b10.set_status(Some(TaskStatus::Todo));
+
+    root.sort_children(SortKey::Name, false, false);
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      ["/", "/a", "/a/z", "/a/y", "/b2", "/b10"]
+    );
+
+    // sibling links follow the new order
+    assert_eq!(a.prev(), None);
+    assert_eq!(a.next(), Some(b2.clone()));
+    assert_eq!(b2.prev(), Some(a.clone()));
+    assert_eq!(b2.next(), Some(b10.clone()));
+    assert_eq!(b10.prev(), Some(b2.clone()));
+    assert_eq!(b10.next(), None);
+
+    root.sort_children(SortKey::Name, true, true);
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      ["/", "/b10", "/b2", "/a", "/a/z", "/a/y"]
+    );
+
+    root.sort_children(SortKey::Status, false, false);
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      ["/", "/b10", "/b2", "/a", "/a/z", "/a/y"]
+    );
+
+    root.sort_children(SortKey::Children, false, false);
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      ["/", "/a", "/a/z", "/a/y", "/b10", "/b2"]
+    );
+
+    // sorting is undone as a whole
+    tree
+      .apply(Operation::Sort {
+        node: root.clone(),
+        key: SortKey::Name,
+        reverse: false,
+        recursive: true,
+      })
+      .unwrap();
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      ["/", "/a", "/a/y", "/a/z", "/b2", "/b10"]
+    );
+    tree.undo().unwrap();
+    assert_eq!(
+      root.paths("/", NodeFilter::default()),
+      ["/", "/a", "/a/z", "/a/y", "/b10", "/b2"]
+    );
+    assert_eq!(z.next(), Some(y.clone()));
 
     assert!(matches!(
-      node.set_data(NodeData::link("https://foo.bar")),
-      Err(NodeError::MismatchDataType)
+      root.reorder_children(vec![a.clone()]),
+      Err(NodeError::NotContainedInParent)
+    ));
+
+    // nodes are touched only when the order of their children changes
+    let node = Node::from_encoding(encoding::Node::new_by_expand_state(
+      "x",
+      false,
+      vec![
+        encoding::Node::new_by_expand_state("a", false, vec![]),
+        encoding::Node::new_by_expand_state("b", false, vec![]),
+      ],
     ));
+    node.sort_children(SortKey::Name, false, true);
+    assert_eq!(node.modified_at(), None);
+    node.sort_children(SortKey::Name, true, true);
+    assert!(node.modified_at().is_some());
+  }
+
+  #[test]
+  fn deep_copy() {
+    let dir = env::temp_dir().join(format!("mind-deep-copy-{}", NodeId::new()));
+    let store = DataFileStore::new(&dir);
+    let path = store.create_data_file("notes", ".md", "# Notes").unwrap();
+
+    let root = Node::new("root", "");
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    let c = Node::new("c", "");
+    root.insert_bottom(a.clone());
+    a.insert_bottom(b.clone());
+    a.insert_bottom(c.clone());
+    b.insert_bottom(c.new_clone());
+    b.add_attachment(NodeData::file(&path)).unwrap();
+    c.add_tag("x").unwrap();
+
+    let copy = a.deep_copy();
+    assert!(copy.parent().is_err());
+    assert_ne!(copy.id(), a.id());
+    assert_eq!(
+      copy.paths("/", NodeFilter::default()),
+      ["/", "/b", "/b/c", "/c"]
+    );
+    assert_eq!(copy.tags(), a.tags());
+
+    let copy_b = copy.children().into_iter().next().cloned().unwrap();
+    let copy_c = copy.children().into_iter().last().cloned().unwrap();
+    assert_eq!(copy_b.parent().unwrap(), copy);
+    assert_eq!(copy_b.next(), Some(copy_c.clone()));
+    assert_eq!(copy_c.prev(), Some(copy_b.clone()));
+    assert_eq!(copy_b.attachments(), b.attachments());
+
+    // clones within the subtree are still clones of each other, but not of the original nodes
+    let copy_b_c = copy_b.children().into_iter().next().cloned().unwrap();
+    assert!(copy_b_c.is_clone_of(&copy_c));
+    assert!(!copy_c.is_clone_of(&c));
+    assert!(copy_c.has_tag("x"));
+
+    // the copy is independent from the original
+    copy_c.set_name("d").unwrap();
+    assert_eq!(c.name(), "c");
+    assert_eq!(copy_b_c.name(), "d");
+
+    // data files can be duplicated as well
+    let copy = b.deep_copy_with_data_files(&store).unwrap();
+    let attachments = copy.attachments();
+    let [NodeData::File(copy_path)] = attachments.as_slice() else {
+      panic!("expected a single data file");
+    };
+    assert_ne!(copy_path, &path);
+    assert_eq!(fs::read_to_string(copy_path).unwrap(), "# Notes");
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  /// Build a tree where node `i` (`i > 0`) is a child of the node selected by `parents[i - 1]` among the nodes before it.
+  fn arbitrary_tree(parents: &[Index]) -> (Tree, Vec<Node>) {
+    let tree = Tree::new("n0", "");
+    let mut nodes = vec![tree.root()];
+
+    for (i, parent) in parents.iter().enumerate() {
+      let node = Node::new(format!("n{}", i + 1), "");
+      parent.get(&nodes).insert_bottom(node.clone());
+      nodes.push(node);
+    }
+
+    (tree, nodes)
+  }
+
+  /// Check the parent and sibling links of a subtree, returning its number of nodes.
+  fn check_links(node: &Node) -> usize {
+    let children: Vec<_> = node.children().into_iter().cloned().collect();
+    let mut count = 1;
+
+    for (i, child) in children.iter().enumerate() {
+      // nodes are compared by name, since their debug representation goes through their siblings back and forth
+      assert_eq!(child.parent().unwrap().name(), node.name());
+      assert_eq!(
+        child.prev().map(|prev| prev.name()),
+        i.checked_sub(1).map(|i| children[i].name())
+      );
+      assert_eq!(
+        child.next().map(|next| next.name()),
+        children.get(i + 1).map(Node::name)
+      );
+      count += check_links(child);
+    }
+
+    count
+  }
+
+  /// Error expected when moving `source` with respect to `dest`, depending on the insert mode.
+  fn expected_move_error(source: &Node, dest: &Node, inside: bool) -> Option<NodeError> {
+    let dest = if inside {
+      dest.clone()
+    } else {
+      match dest.parent() {
+        Ok(parent) => parent,
+        Err(_) => return Some(NodeError::NoParent),
+      }
+    };
+
+    if source.parent().is_err() {
+      Some(NodeError::CannotMoveRoot)
+    } else if source == &dest || source.is_ancestor_of(&dest) {
+      Some(NodeError::CyclicMove)
+    } else {
+      None
+    }
+  }
+
+  proptest! {
+    #[test]
+    fn moves_keep_a_tree(
+      parents in prop::collection::vec(any::<Index>(), 0..20),
+      source in any::<Index>(),
+      dest in any::<Index>(),
+      mode in 0..4_u8,
+    ) {
+      let (tree, nodes) = arbitrary_tree(&parents);
+      let root = tree.root();
+      let source = source.get(&nodes).clone();
+      let dest = dest.get(&nodes).clone();
+      let paths = root.paths("/", NodeFilter::default());
+
+      let expected = expected_move_error(&source, &dest, mode < 2);
+      let result = match mode {
+        0 => dest.move_top(source.clone()),
+        1 => dest.move_bottom(source.clone()),
+        2 => dest.move_before(source.clone()),
+        _ => dest.move_after(source.clone()),
+      };
+
+      prop_assert_eq!(check_links(&root), nodes.len());
+
+      if let Some(err) = expected {
+        // a rejected move leaves the tree untouched
+        prop_assert_eq!(result.unwrap_err().to_string(), err.to_string());
+        prop_assert_eq!(root.paths("/", NodeFilter::default()), paths);
+      } else {
+        prop_assert!(result.is_ok());
+
+        match mode {
+          _ if source == dest => prop_assert_eq!(root.paths("/", NodeFilter::default()), paths),
+          0 => prop_assert!(dest.children().into_iter().next() == Some(&source)),
+          1 => prop_assert!(dest.children().into_iter().last() == Some(&source)),
+          2 => prop_assert!(source.next() == Some(dest)),
+          _ => prop_assert!(source.prev() == Some(dest)),
+        }
+      }
+    }
+
+    #[test]
+    fn move_operations_are_undone(
+      parents in prop::collection::vec(any::<Index>(), 0..20),
+      source in any::<Index>(),
+      dest in any::<Index>(),
+      mode in 0..4_u8,
+    ) {
+      let (tree, nodes) = arbitrary_tree(&parents);
+      let root = tree.root();
+      let source = source.get(&nodes).clone();
+      let dest = dest.get(&nodes).clone();
+      let paths = root.paths("/", NodeFilter::default());
+
+      let expected = expected_move_error(&source, &dest, mode < 2);
+      let result = match mode {
+        0 => Ok(Operation::move_top(dest, source)),
+        1 => Ok(Operation::move_bottom(dest, source)),
+        2 => Operation::move_before(&dest, source),
+        _ => Operation::move_after(&dest, source),
+      }
+      .and_then(|operation| tree.apply(operation));
+
+      prop_assert_eq!(
+        result.map_err(|err| err.to_string()),
+        expected.map_or(Ok(()), |err| Err(err.to_string()))
+      );
+      prop_assert_eq!(check_links(&root), nodes.len());
+
+      tree.undo().unwrap();
+      prop_assert_eq!(check_links(&root), nodes.len());
+      prop_assert_eq!(root.paths("/", NodeFilter::default()), paths);
+    }
   }
 }
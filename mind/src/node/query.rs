@@ -0,0 +1,636 @@
+//! Query language over nodes.
+//!
+//! A query is made of predicates, combined with `and`, `or` and `not` (or `&&`, `||` and `!`) and grouped with
+//! parentheses. `not` binds tighter than `and`, which binds tighter than `or`; predicates next to each other are
+//! implicitly combined with `and`. The supported predicates are:
+//!
+//! - `name:<glob>`: the name of the node matches the glob, where `*` matches any sequence of characters and `?` any
+//!   single character.
+//! - `name:~<regex>`: the name of the node matches the regular expression.
+//! - `depth:<n>`, `depth:<<n>`, `depth:<=<n>`, `depth:><n>` and `depth:>=<n>`: depth of the node in its tree, the root
+//!   being at depth 0.
+//! - `has-children` and `leaf`.
+//! - `data:file`, `data:link`, `data:node`, `data:any` and `data:none`: kind of data attached to the node.
+//! - `under:<path>`: the node is below the node at the given path; segments without an index match all the siblings
+//!   with that name.
+//! - `tag:<tag>`, `prop:<key>` and `prop:<key>=<value>`.
+//!
+//! Values containing spaces, quotes or parentheses must be quoted, e.g. `name:"CI (old)"`; a backslash escapes the
+//! character following it in a quoted value.
+//!
+//! For instance, `under:/Projects and (leaf or data:file) and not name:~^draft`.
+
+use crate::node::{path_iter, Node, NodeData, PathSegment};
+use regex::Regex;
+use std::{iter::Peekable, str::FromStr, vec::IntoIter};
+use thiserror::Error;
+
+/// Errors that might happen when parsing queries.
+#[derive(Debug, Error)]
+pub enum QueryError {
+  #[error("empty query")]
+  Empty,
+
+  #[error("unexpected end of query")]
+  UnexpectedEnd,
+
+  #[error("unexpected '{0}' in query")]
+  UnexpectedToken(String),
+
+  #[error("unterminated quote in query")]
+  UnterminatedQuote,
+
+  #[error("unknown predicate '{0}'")]
+  UnknownPredicate(String),
+
+  #[error("invalid value '{value}' for predicate '{predicate}'")]
+  InvalidValue { predicate: String, value: String },
+
+  #[error("invalid regex: {0}")]
+  InvalidRegex(#[from] regex::Error),
+}
+
+/// A parsed query, which can be matched against nodes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Query {
+  /// The name matches the pattern.
+  Name(NamePattern),
+
+  /// The depth of the node compares to the given depth.
+  Depth(Comparison, usize),
+
+  /// The node has children.
+  HasChildren,
+
+  /// The node has no children.
+  Leaf,
+
+  /// The node has data of the given kind.
+  Data(DataKind),
+
+  /// The node is a descendant of a node at the given path.
+  Under(Vec<PathSegment>),
+
+  /// The node has the given tag.
+  Tag(String),
+
+  /// The node has the given property; if a value is provided, the property must have that value.
+  Property {
+    key: String,
+    value: Option<String>,
+  },
+
+  Not(Box<Query>),
+  And(Box<Query>, Box<Query>),
+  Or(Box<Query>, Box<Query>),
+}
+
+impl Query {
+  /// Check whether a node matches the query.
+  pub fn matches(&self, node: &Node) -> bool {
+    match self {
+      Query::Name(pattern) => pattern.regex.is_match(&node.name()),
+      Query::Depth(cmp, depth) => cmp.holds(Self::ancestors(node).len(), *depth),
+      Query::HasChildren => node.has_children(),
+      Query::Leaf => !node.has_children(),
+      Query::Data(kind) => kind.matches(&node.attachments()),
+      Query::Under(path) => Self::is_under(node, path),
+      Query::Tag(tag) => node.has_tag(tag),
+      Query::Property { key, value } => match (node.property(key), value) {
+        (Some(prop), Some(value)) => &prop == value,
+        (prop, _) => prop.is_some(),
+      },
+      Query::Not(query) => !query.matches(node),
+      Query::And(a, b) => a.matches(node) && b.matches(node),
+      Query::Or(a, b) => a.matches(node) || b.matches(node),
+    }
+  }
+
+  /// Ancestors of a node, from its parent up to the root.
+  fn ancestors(node: &Node) -> Vec<Node> {
+    let mut ancestors = Vec::new();
+    let mut node = node.clone();
+
+    while let Ok(parent) = node.parent() {
+      ancestors.push(parent.clone());
+      node = parent;
+    }
+
+    ancestors
+  }
+
+  fn is_under(node: &Node, path: &[PathSegment]) -> bool {
+    // the node itself, followed by its ancestors, from the root
+    let mut chain = Self::ancestors(node);
+    chain.reverse();
+    chain.push(node.clone());
+
+    // the node must be strictly below the path, which doesn’t include the root
+    if chain.len() <= path.len() + 1 {
+      return false;
+    }
+
+    path.iter().zip(chain.windows(2)).all(|(segment, nodes)| {
+      let node_segment = nodes[1].path_segment(&nodes[0]);
+      node_segment.name == segment.name
+        && segment
+          .index
+          .map_or(true, |index| node_segment.index.unwrap_or(1) == index)
+    })
+  }
+}
+
+impl FromStr for Query {
+  type Err = QueryError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut tokens = tokenize(s)?.into_iter().peekable();
+
+    if tokens.peek().is_none() {
+      return Err(QueryError::Empty);
+    }
+
+    let query = parse_or(&mut tokens)?;
+
+    match tokens.next() {
+      None => Ok(query),
+      Some(token) => Err(QueryError::UnexpectedToken(token.to_string())),
+    }
+  }
+}
+
+/// Pattern to match names with.
+///
+/// Patterns are compared by their source, not their compiled form.
+#[derive(Clone, Debug)]
+pub struct NamePattern {
+  source: String,
+  is_regex: bool,
+  regex: Regex,
+}
+
+impl NamePattern {
+  /// Create a pattern from a glob.
+  pub fn glob(glob: &str) -> Result<Self, QueryError> {
+    let mut regex = String::from("^");
+
+    for c in glob.chars() {
+      match c {
+        '*' => regex.push_str(".*"),
+        '?' => regex.push('.'),
+        _ => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+      }
+    }
+
+    regex.push('$');
+
+    Ok(Self {
+      source: glob.to_owned(),
+      is_regex: false,
+      regex: Regex::new(&regex)?,
+    })
+  }
+
+  /// Create a pattern from a regular expression.
+  pub fn regex(regex: &str) -> Result<Self, QueryError> {
+    Ok(Self {
+      source: regex.to_owned(),
+      is_regex: true,
+      regex: Regex::new(regex)?,
+    })
+  }
+}
+
+impl PartialEq for NamePattern {
+  fn eq(&self, other: &Self) -> bool {
+    self.source == other.source && self.is_regex == other.is_regex
+  }
+}
+
+impl Eq for NamePattern {}
+
+/// Comparison operator.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Comparison {
+  Eq,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+}
+
+impl Comparison {
+  fn holds(self, a: usize, b: usize) -> bool {
+    match self {
+      Comparison::Eq => a == b,
+      Comparison::Lt => a < b,
+      Comparison::Le => a <= b,
+      Comparison::Gt => a > b,
+      Comparison::Ge => a >= b,
+    }
+  }
+}
+
+/// Kind of data attached to a node.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DataKind {
+  File,
+  Link,
+  Node,
+
+  /// Any kind of data.
+  Any,
+
+  /// No data at all.
+  None,
+}
+
+impl DataKind {
+  fn matches(self, attachments: &[NodeData]) -> bool {
+    match self {
+      DataKind::File => attachments
+        .iter()
+        .any(|data| matches!(data, NodeData::File(_))),
+      DataKind::Link => attachments
+        .iter()
+        .any(|data| matches!(data, NodeData::Link(_))),
+      DataKind::Node => attachments
+        .iter()
+        .any(|data| matches!(data, NodeData::Node(_))),
+      DataKind::Any => !attachments.is_empty(),
+      DataKind::None => attachments.is_empty(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+  LParen,
+  RParen,
+  And,
+  Or,
+  Not,
+  Word(String),
+}
+
+impl std::fmt::Display for Token {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Token::LParen => f.write_str("("),
+      Token::RParen => f.write_str(")"),
+      Token::And => f.write_str("and"),
+      Token::Or => f.write_str("or"),
+      Token::Not => f.write_str("not"),
+      Token::Word(word) => f.write_str(word),
+    }
+  }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, QueryError> {
+  let mut tokens = Vec::new();
+  let mut chars = s.chars().peekable();
+
+  while let Some(&c) = chars.peek() {
+    match c {
+      _ if c.is_whitespace() => {
+        chars.next();
+      }
+
+      '(' => {
+        chars.next();
+        tokens.push(Token::LParen);
+      }
+
+      ')' => {
+        chars.next();
+        tokens.push(Token::RParen);
+      }
+
+      '!' => {
+        chars.next();
+        tokens.push(Token::Not);
+      }
+
+      _ => {
+        let mut word = String::new();
+        let mut quoted = false;
+
+        while let Some(&c) = chars.peek() {
+          if c.is_whitespace() || c == '(' || c == ')' {
+            break;
+          }
+
+          chars.next();
+
+          if c == '"' {
+            quoted = true;
+
+            loop {
+              match chars.next() {
+                Some('"') => break,
+                Some('\\') => word.push(chars.next().ok_or(QueryError::UnterminatedQuote)?),
+                Some(c) => word.push(c),
+                None => return Err(QueryError::UnterminatedQuote),
+              }
+            }
+          } else {
+            word.push(c);
+          }
+        }
+
+        let token = match word.as_str() {
+          "and" | "&&" if !quoted => Token::And,
+          "or" | "||" if !quoted => Token::Or,
+          "not" if !quoted => Token::Not,
+          _ => Token::Word(word),
+        };
+        tokens.push(token);
+      }
+    }
+  }
+
+  Ok(tokens)
+}
+
+type Tokens = Peekable<IntoIter<Token>>;
+
+fn parse_or(tokens: &mut Tokens) -> Result<Query, QueryError> {
+  let mut query = parse_and(tokens)?;
+
+  while tokens.next_if_eq(&Token::Or).is_some() {
+    query = Query::Or(Box::new(query), Box::new(parse_and(tokens)?));
+  }
+
+  Ok(query)
+}
+
+fn parse_and(tokens: &mut Tokens) -> Result<Query, QueryError> {
+  let mut query = parse_not(tokens)?;
+
+  loop {
+    match tokens.peek() {
+      Some(Token::And) => {
+        tokens.next();
+      }
+
+      // juxtaposed predicates
+      Some(Token::LParen | Token::Not | Token::Word(_)) => (),
+
+      _ => return Ok(query),
+    }
+
+    query = Query::And(Box::new(query), Box::new(parse_not(tokens)?));
+  }
+}
+
+fn parse_not(tokens: &mut Tokens) -> Result<Query, QueryError> {
+  match tokens.next().ok_or(QueryError::UnexpectedEnd)? {
+    Token::Not => Ok(Query::Not(Box::new(parse_not(tokens)?))),
+
+    Token::LParen => {
+      let query = parse_or(tokens)?;
+
+      match tokens.next() {
+        Some(Token::RParen) => Ok(query),
+        Some(token) => Err(QueryError::UnexpectedToken(token.to_string())),
+        None => Err(QueryError::UnexpectedEnd),
+      }
+    }
+
+    Token::Word(word) => parse_predicate(&word),
+
+    token => Err(QueryError::UnexpectedToken(token.to_string())),
+  }
+}
+
+fn parse_predicate(word: &str) -> Result<Query, QueryError> {
+  let (predicate, value) = match word.split_once(':') {
+    Some((predicate, value)) => (predicate, Some(value)),
+    None => (word, None),
+  };
+  let invalid = || QueryError::InvalidValue {
+    predicate: predicate.to_owned(),
+    value: value.unwrap_or_default().to_owned(),
+  };
+
+  match (predicate, value) {
+    ("has-children", None) => Ok(Query::HasChildren),
+    ("leaf", None) => Ok(Query::Leaf),
+
+    ("name", Some(value)) if !value.is_empty() => match value.strip_prefix('~') {
+      Some(regex) => NamePattern::regex(regex).map(Query::Name),
+      None => NamePattern::glob(value).map(Query::Name),
+    },
+
+    ("depth", Some(value)) => {
+      let (cmp, depth) = [
+        ("<=", Comparison::Le),
+        (">=", Comparison::Ge),
+        ("<", Comparison::Lt),
+        (">", Comparison::Gt),
+        ("=", Comparison::Eq),
+      ]
+      .into_iter()
+      .find_map(|(prefix, cmp)| value.strip_prefix(prefix).map(|depth| (cmp, depth)))
+      .unwrap_or((Comparison::Eq, value));
+
+      depth
+        .parse()
+        .map(|depth| Query::Depth(cmp, depth))
+        .map_err(|_| invalid())
+    }
+
+    ("data", Some(value)) => {
+      let kind = match value {
+        "file" => DataKind::File,
+        "link" | "url" => DataKind::Link,
+        "node" | "ref" => DataKind::Node,
+        "any" => DataKind::Any,
+        "none" => DataKind::None,
+        _ => return Err(invalid()),
+      };
+
+      Ok(Query::Data(kind))
+    }
+
+    ("under", Some(value)) => Ok(Query::Under(path_iter(value).collect())),
+
+    ("tag", Some(value)) if !value.is_empty() => {
+      Ok(Query::Tag(value.trim_start_matches('#').to_owned()))
+    }
+
+    ("prop", Some(value)) if !value.is_empty() => {
+      let (key, value) = match value.split_once('=') {
+        Some((key, value)) => (key, Some(value.to_owned())),
+        None => (value, None),
+      };
+
+      Ok(Query::Property {
+        key: key.to_owned(),
+        value,
+      })
+    }
+
+    ("has-children" | "leaf" | "name" | "depth" | "data" | "under" | "tag" | "prop", _) => {
+      Err(invalid())
+    }
+
+    _ => Err(QueryError::UnknownPredicate(word.to_owned())),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Comparison, DataKind, Query, QueryError};
+  use crate::node::{Node, NodeData, NodeFilter, Tree};
+
+  fn tree() -> Tree {
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let projects = Node::new("Projects", "");
+    let mind = Node::new("mind", "");
+    let readme = Node::new("README.md", "");
+    let draft = Node::new("draft notes", "");
+    let inbox = Node::new("Inbox", "");
+    let link = Node::new("mind.md", "");
+    root.insert_bottom(projects.clone());
+    root.insert_bottom(inbox.clone());
+    projects.insert_bottom(mind.clone());
+    mind.insert_bottom(readme.clone());
+    mind.insert_bottom(draft);
+    inbox.insert_bottom(link.clone());
+
+    readme
+      .add_attachment(NodeData::file("/tmp/README.md"))
+      .unwrap();
+    link
+      .add_attachment(NodeData::link("https://foo.bar"))
+      .unwrap();
+    link.add_tag("later").unwrap();
+    tree
+  }
+
+  fn query(tree: &Tree, query: &str) -> Vec<String> {
+    let query = query.parse::<Query>().unwrap();
+    tree.root().paths("/", NodeFilter::Query(query))
+  }
+
+  #[test]
+  fn parse() {
+    assert_eq!(
+      "depth:<=2 has-children".parse::<Query>().unwrap(),
+      Query::And(
+        Box::new(Query::Depth(Comparison::Le, 2)),
+        Box::new(Query::HasChildren)
+      )
+    );
+    assert_eq!(
+      "not data:file or leaf and data:any"
+        .parse::<Query>()
+        .unwrap(),
+      Query::Or(
+        Box::new(Query::Not(Box::new(Query::Data(DataKind::File)))),
+        Box::new(Query::And(
+          Box::new(Query::Leaf),
+          Box::new(Query::Data(DataKind::Any))
+        ))
+      )
+    );
+    assert_eq!(
+      r#"tag:"or""#.parse::<Query>().unwrap(),
+      Query::Tag("or".to_owned())
+    );
+  }
+
+  #[test]
+  fn parse_errors() {
+    assert!(matches!("".parse::<Query>(), Err(QueryError::Empty)));
+    assert!(matches!(
+      "leaf and".parse::<Query>(),
+      Err(QueryError::UnexpectedEnd)
+    ));
+    assert!(matches!(
+      "(leaf".parse::<Query>(),
+      Err(QueryError::UnexpectedEnd)
+    ));
+    assert!(matches!(
+      "leaf)".parse::<Query>(),
+      Err(QueryError::UnexpectedToken(token)) if token == ")"
+    ));
+    assert!(matches!(
+      r#"name:"foo"#.parse::<Query>(),
+      Err(QueryError::UnterminatedQuote)
+    ));
+    assert!(matches!(
+      "color:red".parse::<Query>(),
+      Err(QueryError::UnknownPredicate(_))
+    ));
+    assert!(matches!(
+      "depth:deep".parse::<Query>(),
+      Err(QueryError::InvalidValue { .. })
+    ));
+    assert!(matches!(
+      r#"name:~"(""#.parse::<Query>(),
+      Err(QueryError::InvalidRegex(_))
+    ));
+  }
+
+  #[test]
+  fn name() {
+    let tree = tree();
+    assert_eq!(
+      query(&tree, "name:*.md"),
+      vec!["/Projects/mind/README.md", "/Inbox/mind.md"]
+    );
+    assert_eq!(query(&tree, "name:mind?md"), vec!["/Inbox/mind.md"]);
+    assert_eq!(
+      query(&tree, r#"name:"draft *""#),
+      vec!["/Projects/mind/draft notes"]
+    );
+    assert_eq!(
+      query(&tree, "name:~^[A-Z]"),
+      vec!["/Projects", "/Projects/mind/README.md", "/Inbox"]
+    );
+  }
+
+  #[test]
+  fn structure() {
+    let tree = tree();
+    assert_eq!(query(&tree, "depth:1"), vec!["/Projects", "/Inbox"]);
+    assert_eq!(
+      query(&tree, "depth:>2"),
+      vec!["/Projects/mind/README.md", "/Projects/mind/draft notes"]
+    );
+    assert_eq!(
+      query(&tree, "has-children and depth:>=1"),
+      vec!["/Projects", "/Projects/mind", "/Inbox"]
+    );
+    assert_eq!(
+      query(&tree, "under:/Projects leaf"),
+      vec!["/Projects/mind/README.md", "/Projects/mind/draft notes"]
+    );
+    assert_eq!(
+      query(&tree, "under:/Projects/mind[1]"),
+      vec!["/Projects/mind/README.md", "/Projects/mind/draft notes"]
+    );
+    assert!(query(&tree, "under:/Projects/mind/README.md").is_empty());
+  }
+
+  #[test]
+  fn data_and_metadata() {
+    let tree = tree();
+    assert_eq!(query(&tree, "data:file"), vec!["/Projects/mind/README.md"]);
+    assert_eq!(
+      query(&tree, "data:any"),
+      vec!["/Projects/mind/README.md", "/Inbox/mind.md"]
+    );
+    assert_eq!(
+      query(&tree, "leaf and !data:any"),
+      vec!["/Projects/mind/draft notes"]
+    );
+    assert_eq!(
+      query(&tree, "tag:later or (data:file and not under:/Inbox)"),
+      vec!["/Projects/mind/README.md", "/Inbox/mind.md"]
+    );
+  }
+}
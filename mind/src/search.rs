@@ -0,0 +1,228 @@
+//! Full-text search over node names and data file contents.
+
+use crate::node::{Node, NodeData};
+use std::{cmp::Reverse, collections::HashSet, fs};
+
+/// Score of a term found in the name of a node.
+const NAME_SCORE: usize = 10;
+
+/// Score of a term found as a whole word in the name of a node, on top of [`NAME_SCORE`].
+const NAME_WORD_SCORE: usize = 5;
+
+/// Maximum number of occurrences of a term counted in a data file, so that long files don’t always win.
+const MAX_FILE_OCCURRENCES: usize = 5;
+
+/// Maximum length of snippets, in characters.
+const SNIPPET_LEN: usize = 80;
+
+/// A node matching a search.
+#[derive(Clone, Debug)]
+pub struct SearchHit {
+  pub node: Node,
+
+  /// Relevance of the hit; the higher, the more relevant.
+  pub score: usize,
+
+  /// Line of a data file of the node matching the search, if any.
+  pub snippet: Option<String>,
+}
+
+/// Search the subtree rooted at `root` for nodes matching all the `terms`.
+///
+/// Terms are matched case-insensitively against node names and the contents of their data files. Matches in names
+/// weigh more than matches in data files. Hits are sorted by decreasing relevance; cloned nodes appear only once.
+pub fn search(root: &Node, terms: &[impl AsRef<str>]) -> Vec<SearchHit> {
+  let terms: Vec<_> = terms
+    .iter()
+    .map(|term| term.as_ref().trim().to_lowercase())
+    .filter(|term| !term.is_empty())
+    .collect();
+
+  if terms.is_empty() {
+    return Vec::new();
+  }
+
+  let mut seen = HashSet::new();
+  let mut hits: Vec<_> = root
+    .subtree()
+    .into_iter()
+    .filter(|node| seen.insert(node.id()))
+    .filter_map(|node| search_node(node, &terms))
+    .collect();
+
+  hits.sort_by_key(|hit| Reverse(hit.score));
+  hits
+}
+
+fn search_node(node: Node, terms: &[String]) -> Option<SearchHit> {
+  let name = node.name().to_lowercase();
+  let contents: Vec<_> = node
+    .attachments()
+    .into_iter()
+    .filter_map(|data| match data {
+      NodeData::File(path) => match fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(err) => {
+          log::debug!("cannot search {}: {err}", path.display());
+          None
+        }
+      },
+      _ => None,
+    })
+    .collect();
+  let lower_contents: Vec<_> = contents
+    .iter()
+    .map(|content| content.to_lowercase())
+    .collect();
+
+  let mut score = 0;
+  for term in terms {
+    let mut term_score = 0;
+
+    if name.contains(term.as_str()) {
+      term_score += NAME_SCORE;
+
+      if name
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word == term)
+      {
+        term_score += NAME_WORD_SCORE;
+      }
+    }
+
+    term_score += lower_contents
+      .iter()
+      .map(|content| {
+        content
+          .matches(term.as_str())
+          .take(MAX_FILE_OCCURRENCES)
+          .count()
+      })
+      .sum::<usize>();
+
+    // all the terms must match
+    if term_score == 0 {
+      return None;
+    }
+
+    score += term_score;
+  }
+
+  Some(SearchHit {
+    snippet: snippet(&contents, terms),
+    node,
+    score,
+  })
+}
+
+/// Find the line of the contents matching the most terms, and shorten it around the first match.
+fn snippet(contents: &[String], terms: &[String]) -> Option<String> {
+  let (line, lower_line, _) = contents
+    .iter()
+    .flat_map(|content| content.lines())
+    .map(|line| {
+      let lower_line = line.to_lowercase();
+      let count = terms
+        .iter()
+        .filter(|term| lower_line.contains(term.as_str()))
+        .count();
+      (line, lower_line, count)
+    })
+    .filter(|(_, _, count)| *count > 0)
+    .max_by_key(|(_, _, count)| *count)?;
+
+  let chars: Vec<_> = line.chars().collect();
+  if chars.len() <= SNIPPET_LEN {
+    return Some(line.trim().to_owned());
+  }
+
+  // center the snippet on the first match
+  let start = terms
+    .iter()
+    .filter_map(|term| lower_line.find(term.as_str()))
+    .min()
+    .map(|byte| lower_line[..byte].chars().count())
+    .unwrap_or(0);
+  let start = start
+    .saturating_sub(SNIPPET_LEN / 4)
+    .min(chars.len() - SNIPPET_LEN);
+  let end = start + SNIPPET_LEN;
+
+  let mut snippet: String = chars[start..end].iter().collect();
+  snippet = snippet.trim().to_owned();
+
+  if start > 0 {
+    snippet.insert(0, '…');
+  }
+
+  if end < chars.len() {
+    snippet.push('…');
+  }
+
+  Some(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::search;
+  use crate::node::{Node, NodeData, NodeId};
+  use std::{env, fs};
+
+  #[test]
+  fn names_and_files() {
+    let path = env::temp_dir().join(format!("mind-search-{}.md", NodeId::new()));
+    fs::write(
+      &path,
+      "# Notes\n\nThe Kafka retention is too low for compacted topics.\nSee the dashboard.\n",
+    )
+    .unwrap();
+
+    let root = Node::new("root", "");
+    let kafka = Node::new("Kafka", "");
+    let bug = Node::new("Retention bug", "");
+    let notes = Node::new("notes", "");
+    let other = Node::new("Kafkaesque", "");
+    root.insert_bottom(kafka.clone());
+    kafka.insert_bottom(bug.clone());
+    kafka.insert_bottom(notes.clone());
+    root.insert_bottom(other.clone());
+    notes.add_attachment(NodeData::file(&path)).unwrap();
+
+    let hits = search(&root, &["kafka"]);
+    let nodes: Vec<_> = hits.iter().map(|hit| hit.node.clone()).collect();
+    assert_eq!(nodes, vec![kafka.clone(), other, notes.clone()]);
+    assert_eq!(hits[0].snippet, None);
+    assert_eq!(
+      hits[2].snippet.as_deref(),
+      Some("The Kafka retention is too low for compacted topics.")
+    );
+
+    // all the terms must match, either in names or files
+    let hits = search(&root, &["KAFKA", "retention"]);
+    let nodes: Vec<_> = hits.iter().map(|hit| hit.node.clone()).collect();
+    assert_eq!(nodes, vec![notes]);
+
+    assert!(search(&root, &["retention", "zookeeper"]).is_empty());
+    assert!(search(&root, &[" "]).is_empty());
+
+    fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn long_snippet() {
+    let path = env::temp_dir().join(format!("mind-search-{}.md", NodeId::new()));
+    let line = format!("{} needle {}", "a".repeat(100), "b".repeat(100));
+    fs::write(&path, &line).unwrap();
+
+    let root = Node::new("root", "");
+    root.add_attachment(NodeData::file(&path)).unwrap();
+
+    let hits = search(&root, &["needle"]);
+    let snippet = hits[0].snippet.as_deref().unwrap();
+    assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+    assert!(snippet.contains("needle"));
+    assert!(snippet.chars().count() <= 82);
+
+    fs::remove_file(path).unwrap();
+  }
+}
@@ -0,0 +1,222 @@
+//! Statistics about the size and shape of trees.
+
+use crate::{
+  forest::Forest,
+  node::{Node, NodeData, Tree},
+};
+use serde::Serialize;
+use std::{cmp::Reverse, collections::HashSet, fs, path::PathBuf};
+
+/// Statistics about one or several trees.
+///
+/// Cloned nodes are counted once per occurrence; data files attached to several nodes are counted once.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+  /// Number of nodes, roots included.
+  pub nodes: usize,
+
+  /// Number of nodes without children.
+  pub leaves: usize,
+
+  /// Depth of the deepest node; roots have a depth of 0.
+  pub max_depth: usize,
+
+  /// Average depth of the nodes.
+  pub average_depth: f64,
+
+  /// Number of nodes with at least one attached file.
+  pub file_nodes: usize,
+
+  /// Number of nodes with at least one attached link.
+  pub link_nodes: usize,
+
+  /// Total size of the attached data files, in bytes; missing files are ignored.
+  pub data_file_size: u64,
+
+  /// Largest subtrees, roots excluded, by decreasing number of descendants.
+  pub largest_subtrees: Vec<SubtreeSize>,
+}
+
+/// Size of a subtree.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SubtreeSize {
+  /// Path of the root of the subtree, prefixed with the directory of its CWD tree, if any, e.g. `/home/me/mind:/a`.
+  pub path: String,
+
+  /// Number of descendants of the root of the subtree.
+  pub descendants: usize,
+}
+
+/// Compute the statistics of a tree, keeping the `largest` biggest subtrees.
+pub fn tree_stats(tree: &Tree, largest: usize) -> Stats {
+  let mut collector = Collector::default();
+  collector.collect_tree("", tree);
+  collector.finish(largest)
+}
+
+/// Compute the statistics of all the trees of a forest, keeping the `largest` biggest subtrees.
+pub fn forest_stats(forest: &Forest, largest: usize) -> Stats {
+  let mut collector = Collector::default();
+  collector.collect_tree("", forest.main_tree());
+
+  let mut projects: Vec<_> = forest.cwd_trees().collect();
+  projects.sort_by_key(|(path, _)| *path);
+
+  for (path, tree) in projects {
+    collector.collect_tree(&format!("{}:", path.display()), tree);
+  }
+
+  collector.finish(largest)
+}
+
+#[derive(Default)]
+struct Collector {
+  stats: Stats,
+  total_depth: usize,
+  data_files: HashSet<PathBuf>,
+  subtrees: Vec<SubtreeSize>,
+}
+
+impl Collector {
+  fn collect_tree(&mut self, prefix: &str, tree: &Tree) {
+    let root = tree.root();
+    for child in root.children().into_iter() {
+      self.collect_node(prefix, child, 1);
+    }
+
+    self.collect_content(&root, 0);
+  }
+
+  /// Collect the statistics of a node and its descendants, returning its number of descendants.
+  fn collect_node(&mut self, prefix: &str, node: &Node, depth: usize) -> usize {
+    // subtrees are kept in the order of the tree, so that ties are sorted that way
+    let index = self.subtrees.len();
+    self.subtrees.push(SubtreeSize {
+      path: format!("{prefix}{}", node.path()),
+      descendants: 0,
+    });
+
+    let descendants = node
+      .children()
+      .into_iter()
+      .map(|child| 1 + self.collect_node(prefix, child, depth + 1))
+      .sum();
+
+    self.collect_content(node, depth);
+    self.subtrees[index].descendants = descendants;
+    descendants
+  }
+
+  fn collect_content(&mut self, node: &Node, depth: usize) {
+    self.stats.nodes += 1;
+    self.stats.max_depth = self.stats.max_depth.max(depth);
+    self.total_depth += depth;
+
+    if !node.has_children() {
+      self.stats.leaves += 1;
+    }
+
+    let attachments = node.attachments();
+    if attachments
+      .iter()
+      .any(|data| matches!(data, NodeData::File(_)))
+    {
+      self.stats.file_nodes += 1;
+    }
+
+    if attachments
+      .iter()
+      .any(|data| matches!(data, NodeData::Link(_)))
+    {
+      self.stats.link_nodes += 1;
+    }
+
+    for data in attachments {
+      if let NodeData::File(path) = data {
+        if !self.data_files.contains(&path) {
+          if let Ok(metadata) = fs::metadata(&path) {
+            self.stats.data_file_size += metadata.len();
+          }
+
+          self.data_files.insert(path);
+        }
+      }
+    }
+  }
+
+  fn finish(mut self, largest: usize) -> Stats {
+    if self.stats.nodes > 0 {
+      self.stats.average_depth = self.total_depth as f64 / self.stats.nodes as f64;
+    }
+
+    self
+      .subtrees
+      .sort_by_key(|subtree| Reverse(subtree.descendants));
+    self.subtrees.truncate(largest);
+    self.stats.largest_subtrees = self.subtrees;
+
+    self.stats
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{forest_stats, tree_stats, SubtreeSize};
+  use crate::{
+    forest::Forest,
+    node::{Node, NodeData, NodeId, Tree},
+  };
+  use std::{env, fs};
+
+  #[test]
+  fn stats() {
+    let path = env::temp_dir().join(format!("mind-stats-{}.md", NodeId::new()));
+    fs::write(&path, "12345").unwrap();
+
+    let tree = Tree::new("root", "");
+    let root = tree.root();
+    let a = Node::new("a", "");
+    let b = Node::new("b", "");
+    let c = Node::new("c", "");
+    let d = Node::new("d", "");
+    root.insert_bottom(a.clone());
+    root.insert_bottom(b.clone());
+    a.insert_bottom(c.clone());
+    c.insert_bottom(d.clone());
+    b.add_attachment(NodeData::file(&path)).unwrap();
+    b.add_attachment(NodeData::link("https://foo.bar")).unwrap();
+    d.add_attachment(NodeData::file(&path)).unwrap();
+
+    let stats = tree_stats(&tree, 2);
+    assert_eq!(stats.nodes, 5);
+    assert_eq!(stats.leaves, 2);
+    assert_eq!(stats.max_depth, 3);
+    assert_eq!(stats.average_depth, 7. / 5.);
+    assert_eq!(stats.file_nodes, 2);
+    assert_eq!(stats.link_nodes, 1);
+    assert_eq!(stats.data_file_size, 5);
+    assert_eq!(
+      stats.largest_subtrees,
+      [
+        SubtreeSize {
+          path: "/a".to_owned(),
+          descendants: 2
+        },
+        SubtreeSize {
+          path: "/a/c".to_owned(),
+          descendants: 1
+        },
+      ]
+    );
+
+    let mut forest = Forest::new(tree);
+    forest.add_cwd_tree("/project", Tree::new("project", ""));
+    let stats = forest_stats(&forest, 10);
+    assert_eq!(stats.nodes, 6);
+    assert_eq!(stats.leaves, 3);
+    assert_eq!(stats.average_depth, 7. / 6.);
+    assert_eq!(stats.largest_subtrees.len(), 4);
+
+    fs::remove_file(path).unwrap();
+  }
+}